@@ -0,0 +1,43 @@
+use std::str::FromStr;
+
+// steps run by a bare `--headless`
+const DEFAULT_HEADLESS_STEPS: usize = 1000;
+
+// Options given on the command line, e.g. `--width 800 --height 600 --bodies 200` or
+// `--scene my_scene.ron`. Anything missing or that doesn't parse is None so the caller's default
+// is used.
+pub struct Args {
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    pub bodies: Option<usize>,
+    pub scene: Option<String>,
+    // `--headless [steps]` runs that many steps without opening a window
+    pub headless: Option<usize>,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        Args {
+            width: value_of(&args, "--width").filter(|width: &f32| *width > 0.0),
+            height: value_of(&args, "--height").filter(|height: &f32| *height > 0.0),
+            bodies: value_of(&args, "--bodies"),
+            scene: value_of(&args, "--scene"),
+            headless: value_of(&args, "--headless").or_else(|| {
+                if args.iter().any(|arg| arg == "--headless") {
+                    Some(DEFAULT_HEADLESS_STEPS)
+                } else {
+                    None
+                }
+            }),
+        }
+    }
+}
+
+// the argument following name
+fn value_of<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .and_then(|value| value.parse().ok())
+}
@@ -1,9 +1,11 @@
-use ggez::graphics::Color;
-use ggez::nalgebra::{Point2, Vector2};
+use crate::ecs::graphics::Color;
+use nalgebra::{Point2, Vector2};
 
 use std::collections::VecDeque;
+use std::fmt;
 
 use specs::prelude::*;
+use specs::saveload::{Marker, SimpleMarker};
 use specs::Component;
 
 use serde::{Deserialize, Serialize};
@@ -13,6 +15,24 @@ pub type Vector = Vector2<f32>;
 
 pub struct SaveMarker;
 
+// A stable id for a body. Entities are reused and change between saves, this is the id of the
+// body's save marker, which is allocated from a counter when the body is created and kept through
+// saving and loading.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BodyId(pub u64);
+
+impl From<&SimpleMarker<SaveMarker>> for BodyId {
+    fn from(marker: &SimpleMarker<SaveMarker>) -> Self {
+        BodyId(marker.id())
+    }
+}
+
+impl fmt::Display for BodyId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Component, Serialize, Deserialize)]
 #[storage(NullStorage)]
 pub struct Preview;
@@ -37,6 +57,7 @@ impl Into<Point> for Position {
     }
 }
 
+#[cfg(feature = "gui")]
 impl Into<ggez::mint::Point2<f32>> for Position {
     fn into(self) -> ggez::mint::Point2<f32> {
         [self.0.x, self.0.y].into()
@@ -75,19 +96,54 @@ pub struct Mass(pub f32);
 #[storage(VecStorage)]
 pub struct Draw(pub Color);
 
+// doesn't move but still attracts everything else, as if it had infinite inertia
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Component, Serialize, Deserialize)]
 #[storage(NullStorage)]
-pub struct Static;
+pub struct Anchored;
 
 #[derive(Clone, Copy, Debug, PartialEq, Component, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct Radius(pub f32);
 
+// a name drawn next to the body, most bodies don't have one
+#[derive(Clone, Debug, PartialEq, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct Label(pub String);
+
+// Membership in a rigid aggregate of bodies that collided in CollisionMode::Aggregate. Every
+// member of a group has the same angle, offset is the member's position relative to the group's
+// center of mass when the angle was 0.
+#[derive(Clone, Copy, Debug, PartialEq, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct Aggregate {
+    pub group: u64,
+    pub offset: Vector,
+    pub angle: f32,
+}
+
 #[derive(Clone, Debug, PartialEq, Component, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct Trail {
+    // trails are rebuilt as the sim runs, so only their length is saved
+    #[serde(skip)]
     pub points: VecDeque<Point>,
     pub max_len: usize,
+    // a disabled trail collects no points and isn't drawn
+    #[serde(default = "trail_enabled_default")]
+    pub enabled: bool,
+    #[serde(default = "trail_color_default")]
+    pub color: Color,
+}
+
+// the color every trail starts with
+pub const TRAIL_COLOR: Color = Color::new(0.25, 0.45, 1.0, 1.0);
+
+fn trail_enabled_default() -> bool {
+    true
+}
+
+fn trail_color_default() -> Color {
+    TRAIL_COLOR
 }
 
 impl Trail {
@@ -95,6 +151,8 @@ impl Trail {
         Trail {
             points: VecDeque::with_capacity(len),
             max_len: len,
+            enabled: true,
+            color: TRAIL_COLOR,
         }
     }
 }
@@ -127,5 +185,6 @@ make_graph_components!(
     [SpeedGraph, f32],
     [XVelGraph, f32],
     [YVelGraph, f32],
-    [AccelGraph, Vector]
+    [AccelGraph, Vector],
+    [AccelMagGraph, f32]
 );
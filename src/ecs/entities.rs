@@ -4,7 +4,7 @@ use crate::{Point, Vector};
 use specs::prelude::*;
 use specs::saveload::{MarkedBuilder, SimpleMarker};
 
-use ggez::graphics;
+use crate::ecs::graphics::{self, WHITE};
 
 pub type Body = (Position, Kinematics, Mass, Draw, Radius, Trail);
 pub type PreviewBody = (Position, Kinematics, Radius, Preview, Draw, Trail);
@@ -22,6 +22,13 @@ pub fn create_body(world: &mut World, body: Body) -> Entity {
         .build()
 }
 
+pub fn body_id(world: &World, entity: Entity) -> Option<BodyId> {
+    world
+        .read_storage::<SimpleMarker<SaveMarker>>()
+        .get(entity)
+        .map(BodyId::from)
+}
+
 pub fn create_preview(world: &mut World, body: PreviewBody) -> Entity {
     world
         .create_entity()
@@ -39,7 +46,7 @@ pub fn new_body(pos: impl Into<Point>, vel: impl Into<Vector>, mass: f32, rad: f
         Position(pos.into()),
         Kinematics::new(vel.into()),
         Mass(mass),
-        Draw(ggez::graphics::WHITE),
+        Draw(WHITE),
         Radius(rad),
         Trail::new(35),
     )
@@ -55,3 +62,21 @@ pub fn new_preview(pos: impl Into<Point>, vel: impl Into<Vector>, rad: f32) -> P
         Trail::new(500),
     )
 }
+
+// Adds a solid body rotation of omega radians per step about the clusters center of mass on top
+// of each bodies own velocity. Meant for the cluster generators to call at spawn time.
+pub fn add_spin(bodies: &mut [Body], omega: f32) {
+    let total_mass: f32 = bodies.iter().map(|body| (body.2).0).sum();
+    if total_mass == 0.0 {
+        return;
+    }
+
+    let center = bodies.iter().fold(Vector::new(0.0, 0.0), |acc, body| {
+        acc + (body.0).0.coords * (body.2).0
+    }) / total_mass;
+
+    bodies.iter_mut().for_each(|body| {
+        let r = (body.0).0.coords - center;
+        (body.1).vel += Vector::new(-r.y, r.x) * omega;
+    });
+}
@@ -0,0 +1,49 @@
+// The few graphics types the simulation itself uses. With the gui feature they're ggez's so bodies
+// can be drawn as they are, without it these stand-ins have the same fields and save the same way.
+
+#[cfg(feature = "gui")]
+pub use ggez::graphics::{Color, Rect, WHITE};
+
+#[cfg(not(feature = "gui"))]
+pub use self::stand_ins::{Color, Rect, WHITE};
+
+#[cfg(not(feature = "gui"))]
+mod stand_ins {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Color {
+        pub r: f32,
+        pub g: f32,
+        pub b: f32,
+        pub a: f32,
+    }
+
+    impl Color {
+        pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+            Color { r, g, b, a }
+        }
+    }
+
+    impl From<[f32; 4]> for Color {
+        fn from(c: [f32; 4]) -> Self {
+            Color::new(c[0], c[1], c[2], c[3])
+        }
+    }
+
+    pub const WHITE: Color = Color::new(1.0, 1.0, 1.0, 1.0);
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub struct Rect {
+        pub x: f32,
+        pub y: f32,
+        pub w: f32,
+        pub h: f32,
+    }
+
+    impl Rect {
+        pub const fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+            Rect { x, y, w, h }
+        }
+    }
+}
@@ -1,4 +1,6 @@
 pub mod components;
 pub mod entities;
+pub mod graphics;
 pub mod resources;
 pub mod systems;
+pub mod world;
@@ -1,11 +1,33 @@
+use crate::ecs::components::BodyId;
 use crate::{Point, Vector};
+use specs::Entity;
+
+use std::collections::VecDeque;
+use std::time::Duration;
 
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct PreviewIterations(pub usize);
 
+// number of collision resolution passes per physics step, extra passes resolve the overlaps
+// created by the previous pass's merges
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct CollisionIterations(pub usize);
+
+// whole steps of DT per frame, more makes the sim run faster
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct MainIterations(pub usize);
 
+// Each step is split into this many substeps of DT / Substeps. Unlike MainIterations this doesn't
+// change how much time a step covers, it only integrates fast bodies more accurately.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Substeps(pub u32);
+
+impl Default for Substeps {
+    fn default() -> Self {
+        Substeps(1)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct StartPoint(pub Option<Point>);
 impl Default for StartPoint {
@@ -34,6 +56,20 @@ impl Default for MousePos {
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct DT(pub f32);
 
+// FixedStep advances DT every step. RealTime scales DT by how long the last frame took so the sim
+// keeps the same speed at any framerate, which makes runs non-deterministic.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeMode {
+    FixedStep,
+    RealTime,
+}
+
+impl Default for TimeMode {
+    fn default() -> Self {
+        TimeMode::FixedStep
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct Paused(pub bool);
 
@@ -43,6 +79,58 @@ impl Paused {
     }
 }
 
+// eases scroll wheel zoom in over a few frames instead of jumping
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct ZoomSmoothing(pub bool);
+
+// pauses while the window is unfocused and resumes when it's focused again
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct PauseOnFocusLoss(pub bool);
+
+// F11 switches between the window and fullscreen on the desktop's resolution
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Fullscreen(pub bool);
+
+impl Fullscreen {
+    pub fn toggle(&mut self) {
+        self.0 = !self.0;
+    }
+}
+
+// velocity given to a new body per unit dragged, the preview uses it too so it matches the launch
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LaunchScale(pub f32);
+
+impl Default for LaunchScale {
+    fn default() -> Self {
+        LaunchScale(0.025)
+    }
+}
+
+// how much Ctrl + an arrow key changes the selected body's velocity by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NudgeStep(pub f32);
+
+impl Default for NudgeStep {
+    fn default() -> Self {
+        NudgeStep(0.01)
+    }
+}
+
+// Which way dragging out a new body launches it. Slingshot pulls back so it launches away from the
+// mouse, Direct launches it towards the mouse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AimMode {
+    Slingshot,
+    Direct,
+}
+
+impl Default for AimMode {
+    fn default() -> Self {
+        AimMode::Slingshot
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct NewPreview(pub bool);
 
@@ -58,12 +146,152 @@ impl FollowSelectedBody {
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct EnableTrails(pub bool);
 
+// The most trail points kept across every body, about 8 bytes each. Past it the longest trails
+// are shortened first, so many bodies with long trails can't grow memory without bound.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaxTrailPoints(pub usize);
+
+impl Default for MaxTrailPoints {
+    fn default() -> Self {
+        MaxTrailPoints(500_000)
+    }
+}
+
 impl EnableTrails {
     pub fn toggle(&mut self) {
         self.0 = !self.0;
     }
 }
 
+// When enabled, bodies drawn smaller than a cell of cell_pixels on the screen are grouped by
+// cell and each group is drawn as one dot, so zooming in splits them back up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lod {
+    pub enabled: bool,
+    pub cell_pixels: f32,
+}
+
+impl Default for Lod {
+    fn default() -> Self {
+        Lod {
+            enabled: false,
+            cell_pixels: 4.0,
+        }
+    }
+}
+
+// lines every spacing world units, fixed in the world so they move with the camera
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+    pub enabled: bool,
+    pub spacing: f32,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Grid {
+            enabled: false,
+            spacing: 25.0,
+        }
+    }
+}
+
+// Arrows on the selected body along its velocity and acceleration. The velocity arrow is how far
+// it moves in scale steps, the acceleration arrow how far that accel alone would move it, so both
+// are in world units and the same scale suits both.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionArrows {
+    pub enabled: bool,
+    pub scale: f32,
+}
+
+impl Default for MotionArrows {
+    fn default() -> Self {
+        MotionArrows {
+            enabled: false,
+            scale: 10.0,
+        }
+    }
+}
+
+// Shades the screen by the gravitational potential, sampled on a grid resolution cells across. It's
+// O(cells * bodies) so it's kept coarse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PotentialMap {
+    pub enabled: bool,
+    pub resolution: usize,
+}
+
+impl Default for PotentialMap {
+    fn default() -> Self {
+        PotentialMap {
+            enabled: false,
+            resolution: 32,
+        }
+    }
+}
+
+// trails narrow from the body to the tail
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct TrailTaper(pub bool);
+
+impl TrailTaper {
+    pub fn toggle(&mut self) {
+        self.0 = !self.0;
+    }
+}
+
+// Solid draws trails in one color, Fade dims them to transparent towards their oldest point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrailStyle {
+    Solid,
+    Fade,
+}
+
+impl Default for TrailStyle {
+    fn default() -> Self {
+        TrailStyle::Solid
+    }
+}
+
+impl TrailStyle {
+    pub fn toggle(&mut self) {
+        *self = match self {
+            TrailStyle::Solid => TrailStyle::Fade,
+            TrailStyle::Fade => TrailStyle::Solid,
+        };
+    }
+}
+
+// which bodies record and draw trails
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrailScope {
+    All,
+    SelectedOnly,
+}
+
+impl Default for TrailScope {
+    fn default() -> Self {
+        TrailScope::All
+    }
+}
+
+impl TrailScope {
+    pub fn toggle(&mut self) {
+        *self = match self {
+            TrailScope::All => TrailScope::SelectedOnly,
+            TrailScope::SelectedOnly => TrailScope::All,
+        };
+    }
+
+    pub fn includes(self, entity: Entity, selected: SelectedEntity) -> bool {
+        match self {
+            TrailScope::All => true,
+            TrailScope::SelectedOnly => selected.0 == Some(entity),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct RelativeTrails {
     pub enabled: bool,
@@ -82,3 +310,458 @@ impl RelativeTrails {
         self.enabled = !self.enabled;
     }
 }
+
+// draws where the selected body will go over the next PreviewIterations steps
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct PredictSelected(pub bool);
+
+impl PredictSelected {
+    pub fn toggle(&mut self) {
+        self.0 = !self.0;
+    }
+}
+
+// FPS and body count in the corner of the screen
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct ShowFps(pub bool);
+
+impl ShowFps {
+    pub fn toggle(&mut self) {
+        self.0 = !self.0;
+    }
+}
+
+// draws a crosshair at the center of mass of every body
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct ShowCenterOfMass(pub bool);
+
+impl ShowCenterOfMass {
+    pub fn toggle(&mut self) {
+        self.0 = !self.0;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Metaballs(pub bool);
+
+impl Metaballs {
+    pub fn toggle(&mut self) {
+        self.0 = !self.0;
+    }
+}
+
+// How body radii are drawn. Log compresses the range of sizes so tiny and huge bodies can be
+// seen at once, the physics always uses the true radius.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RadiusDisplayMode {
+    True,
+    Log,
+}
+
+impl Default for RadiusDisplayMode {
+    fn default() -> Self {
+        RadiusDisplayMode::True
+    }
+}
+
+impl RadiusDisplayMode {
+    pub fn toggle(&mut self) {
+        *self = match self {
+            RadiusDisplayMode::True => RadiusDisplayMode::Log,
+            RadiusDisplayMode::Log => RadiusDisplayMode::True,
+        };
+    }
+
+    pub fn display_radius(self, rad: f32) -> f32 {
+        match self {
+            RadiusDisplayMode::True => rad,
+            // keeps the sign for negative radii and stays close to the true radius around 1
+            RadiusDisplayMode::Log => rad.signum() * 2.0 * (1.0 + rad.abs()).ln(),
+        }
+    }
+}
+
+// How bodies are colored. Speed tints every body from blue when still to red at the fastest speed
+// in the world, the Draw colors are left alone and come back when it's switched off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorDisplayMode {
+    Draw,
+    Speed,
+}
+
+impl Default for ColorDisplayMode {
+    fn default() -> Self {
+        ColorDisplayMode::Draw
+    }
+}
+
+impl ColorDisplayMode {
+    pub fn toggle(&mut self) {
+        *self = match self {
+            ColorDisplayMode::Draw => ColorDisplayMode::Speed,
+            ColorDisplayMode::Speed => ColorDisplayMode::Draw,
+        };
+    }
+}
+
+// mirrors MainState::selected_entity so that systems can read it
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct SelectedEntity(pub Option<Entity>);
+
+// the body whose velocity is subtracted from the graphed velocities, None graphs them as they are
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct ReferenceFrame(pub Option<Entity>);
+
+// when enabled, only the selected body moves through the field of the others, which are frozen
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct IsolateSelected(pub bool);
+
+impl IsolateSelected {
+    pub fn toggle(&mut self) {
+        self.0 = !self.0;
+    }
+}
+
+// How many steps back the rewind can go, 0 turns it off and is the default. Every step keeps a copy
+// of every body at about 190 bytes each, so the memory used is bodies * steps * 190, e.g. 110MB for
+// 1000 bodies with 600 steps.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct RewindDepth(pub usize);
+
+// periodically saves the universe into rotating checkpoint files,
+// an interval of 0 disables that trigger
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoSave {
+    pub enabled: bool,
+    pub interval_steps: usize,
+    pub interval_mins: f32,
+    pub keep: usize,
+}
+
+impl Default for AutoSave {
+    fn default() -> Self {
+        AutoSave {
+            enabled: false,
+            interval_steps: 10_000,
+            interval_mins: 5.0,
+            keep: 3,
+        }
+    }
+}
+
+// what happens to bodies that touch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionMode {
+    // completely inelastic, the bodies become one circle
+    Merge,
+    // the bodies stick together as a rigid aggregate but are still separate circles
+    Aggregate,
+    // perfectly elastic, the bodies bounce off each other and are pushed apart
+    Bounce,
+}
+
+impl Default for CollisionMode {
+    fn default() -> Self {
+        CollisionMode::Merge
+    }
+}
+
+// What happens to bodies at the edges of WorldSize. None leaves space infinite, Wrap moves a body
+// leaving one side to the opposite side and Reflect bounces it off the walls.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    None,
+    Wrap,
+    Reflect,
+}
+
+impl Default for Boundary {
+    fn default() -> Self {
+        Boundary::None
+    }
+}
+
+// the box the Boundary is enforced on, from the origin to this corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldSize(pub Vector);
+
+impl Default for WorldSize {
+    fn default() -> Self {
+        WorldSize(Vector::new(crate::SCREEN_X, crate::SCREEN_Y))
+    }
+}
+
+// the gravitational constant, negative values make gravity repulsive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GravityConstant(pub f32);
+
+impl Default for GravityConstant {
+    fn default() -> Self {
+        GravityConstant(crate::G)
+    }
+}
+
+// Gravitational softening length, the pull between bodies goes as 1 / (r^2 + softening^2) so it
+// levels off at close range instead of flinging near misses away.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Softening(pub f32);
+
+impl Default for Softening {
+    fn default() -> Self {
+        Softening(1.0)
+    }
+}
+
+// How positions and velocities are stepped. Each step integrates positions, recalculates
+// gravity and then integrates velocities, the integrators differ in which accelerations they use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integrator {
+    // explicit, only uses the acceleration from the start of the step, drifts the most
+    Euler,
+    // the velocity is kicked by the acceleration after the drift, so it's half a step ahead
+    Leapfrog,
+    // averages the acceleration from before and after the step
+    VelocityVerlet,
+    // 4th order Runge-Kutta, recalculates gravity over the whole system at three trial states
+    // within the step so it's about four times slower than the others but far more accurate
+    RK4,
+}
+
+impl Default for Integrator {
+    fn default() -> Self {
+        Integrator::VelocityVerlet
+    }
+}
+
+// how apply_gravity sums the pull of every body
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GravityMode {
+    // every pair, exact but O(n^2)
+    BruteForce,
+    // approximates distant groups of bodies by their center of mass, O(n log n)
+    BarnesHut,
+}
+
+impl Default for GravityMode {
+    fn default() -> Self {
+        GravityMode::BruteForce
+    }
+}
+
+// Opening angle for GravityMode::BarnesHut. A group is approximated when its width over its
+// distance is below this, so 0 is exact and higher is faster but rougher.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BarnesHutTheta(pub f32);
+
+impl Default for BarnesHutTheta {
+    fn default() -> Self {
+        BarnesHutTheta(0.5)
+    }
+}
+
+// Strength of a crude gravitational wave style energy loss, close pairs lose relative velocity
+// at a rate that grows steeply as they get closer so tight binaries spiral in. 0 disables it.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct InspiralDamping(pub f32);
+
+// When set, bodies are uniform disks of this density and their radius follows their mass, including
+// after merging or editing the mass. None leaves the radius independent of the mass.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Density(pub Option<f32>);
+
+impl Density {
+    pub fn radius(&self, mass: f32) -> Option<f32> {
+        self.0
+            .map(|density| (mass / (density * std::f32::consts::PI)).sqrt())
+    }
+}
+
+// Velocities are clamped to this speed after each step, keeping their direction, so a close
+// encounter can't fling a body through others in one step. This breaks momentum conservation
+// whenever it clamps, it's a stability guard rather than physics. None disables it.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct MaxSpeed(pub Option<f32>);
+
+// Merging bodies collide when they're within this many times the sum of their radii. Below 1 they
+// overlap before merging, above 1 they merge before touching.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MergeThreshold(pub f32);
+
+impl Default for MergeThreshold {
+    fn default() -> Self {
+        MergeThreshold(1.0)
+    }
+}
+
+// Merging bodies only stick when they hit slower than this relative speed, faster ones bounce off
+// each other elastically instead. None merges at any speed.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct StickThreshold(pub Option<f32>);
+
+// bodies merged above this mass break apart into fragments below it, None disables it
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct MaxBodyMass(pub Option<f32>);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CollisionEvent {
+    pub bodies: (BodyId, BodyId),
+    pub dissipated: f32,
+}
+
+// the most recent collisions, oldest first
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollisionLog {
+    pub events: VecDeque<CollisionEvent>,
+    pub max_len: usize,
+}
+
+impl Default for CollisionLog {
+    fn default() -> Self {
+        CollisionLog {
+            events: VecDeque::with_capacity(50),
+            max_len: 50,
+        }
+    }
+}
+
+impl CollisionLog {
+    pub fn push(&mut self, event: CollisionEvent) {
+        self.events.push_back(event);
+        while self.events.len() > self.max_len {
+            self.events.pop_front();
+        }
+    }
+}
+
+// What happens to a body whose position or velocity has gone NaN or infinite. Delete removes it,
+// Clamp replaces the bad values with finite ones and Ignore leaves it, they're all logged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NanPolicy {
+    Delete,
+    Clamp,
+    Ignore,
+}
+
+impl Default for NanPolicy {
+    fn default() -> Self {
+        NanPolicy::Delete
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NonFiniteEvent {
+    pub body: BodyId,
+    pub policy: NanPolicy,
+}
+
+// the most recent bodies found with non-finite values, oldest first
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiagnosticsLog {
+    pub events: VecDeque<NonFiniteEvent>,
+    pub max_len: usize,
+}
+
+impl Default for DiagnosticsLog {
+    fn default() -> Self {
+        DiagnosticsLog {
+            events: VecDeque::with_capacity(50),
+            max_len: 50,
+        }
+    }
+}
+
+impl DiagnosticsLog {
+    pub fn push(&mut self, event: NonFiniteEvent) {
+        self.events.push_back(event);
+        while self.events.len() > self.max_len {
+            self.events.pop_front();
+        }
+    }
+}
+
+// How long each phase of do_physics took over the last frame, summed over every step and substep.
+// Nothing is timed unless it's enabled so it costs nothing when it's off.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct PhysicsTimings {
+    pub enabled: bool,
+    pub integrate_positions: Duration,
+    pub apply_gravity: Duration,
+    pub integrate_kinematics: Duration,
+    pub calc_collisions: Duration,
+    pub update_trails: Duration,
+}
+
+impl PhysicsTimings {
+    // the timed phases with their names, in the order they run
+    pub fn phases(&self) -> [(&'static str, Duration); 5] {
+        [
+            ("integrate_positions", self.integrate_positions),
+            ("apply_gravity", self.apply_gravity),
+            ("integrate_kinematics", self.integrate_kinematics),
+            ("calc_collisions", self.calc_collisions),
+            ("update_trails", self.update_trails),
+        ]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases().iter().map(|(_, time)| *time).sum()
+    }
+
+    // zeroes the times for a new frame, it stays enabled
+    pub fn reset(&mut self) {
+        *self = PhysicsTimings {
+            enabled: self.enabled,
+            ..PhysicsTimings::default()
+        };
+    }
+}
+
+// Energy and momentum of the whole system. dissipated is the total kinetic energy lost in
+// collisions and to inspiral damping, so any change in total() + dissipated is integration error.
+// Angular momentum is about the center of mass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SystemStats {
+    pub kinetic: f32,
+    pub potential: f32,
+    pub dissipated: f32,
+    pub momentum: Vector,
+    pub angular_momentum: f32,
+    pub center_of_mass: Point,
+}
+
+impl Default for SystemStats {
+    fn default() -> Self {
+        SystemStats {
+            kinetic: 0.0,
+            potential: 0.0,
+            dissipated: 0.0,
+            momentum: Vector::new(0.0, 0.0),
+            angular_momentum: 0.0,
+            center_of_mass: Point::new(0.0, 0.0),
+        }
+    }
+}
+
+impl SystemStats {
+    pub fn total(&self) -> f32 {
+        self.kinetic + self.potential
+    }
+}
+
+// appends SystemStats to a csv every interval_steps steps, up to max_rows rows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StatsLog {
+    pub enabled: bool,
+    pub interval_steps: usize,
+    pub max_rows: usize,
+}
+
+impl Default for StatsLog {
+    fn default() -> Self {
+        StatsLog {
+            enabled: false,
+            interval_steps: 100,
+            max_rows: 100_000,
+        }
+    }
+}
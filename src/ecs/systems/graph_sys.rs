@@ -1,4 +1,7 @@
-use crate::ecs::components::{AccelGraph, Kinematics, SpeedGraph, XVelGraph, YVelGraph};
+use crate::ecs::components::{
+    AccelGraph, AccelMagGraph, Kinematics, SpeedGraph, XVelGraph, YVelGraph,
+};
+use crate::ecs::resources::ReferenceFrame;
 use crate::Vector;
 use specs::prelude::*;
 
@@ -15,17 +18,29 @@ pub enum GraphType {
     XVel,
     YVel,
     Accel,
+    AccelMag,
 }
 
 macro_rules! make_graphsys {
     ( $sys:ident, $comp:ty, $access:ident, $graph_len:expr) => {
         pub struct $sys;
         impl<'a> System<'a> for $sys {
-            type SystemData = (ReadStorage<'a, Kinematics>, WriteStorage<'a, $comp>);
+            type SystemData = (
+                ReadStorage<'a, Kinematics>,
+                WriteStorage<'a, $comp>,
+                Read<'a, ReferenceFrame>,
+            );
+
+            fn run(&mut self, (kinematics, mut graphs, reference_frame): Self::SystemData) {
+                // velocities are graphed relative to the reference body
+                let frame_vel = reference_frame
+                    .0
+                    .and_then(|e| kinematics.get(e))
+                    .map(|kine| kine.vel)
+                    .unwrap_or_else(|| Vector::new(0.0, 0.0));
 
-            fn run(&mut self, (kinematics, mut graphs): Self::SystemData) {
                 (&kinematics, &mut graphs).join().for_each(|(kine, graph)| {
-                    graph.data.push($access(kine));
+                    graph.data.push($access(kine, frame_vel));
 
                     while graph.data.len() >= $graph_len {
                         graph.data.remove(0);
@@ -36,22 +51,27 @@ macro_rules! make_graphsys {
     };
 }
 
-fn norm_access(kine: &Kinematics) -> f32 {
-    kine.vel.norm()
+fn norm_access(kine: &Kinematics, frame_vel: Vector) -> f32 {
+    (kine.vel - frame_vel).norm()
 }
 make_graphsys!(SpeedGraphSys, SpeedGraph, norm_access, 500);
 
-fn x_vel_access(kine: &Kinematics) -> f32 {
-    kine.vel.x
+fn x_vel_access(kine: &Kinematics, frame_vel: Vector) -> f32 {
+    kine.vel.x - frame_vel.x
 }
 make_graphsys!(XVelGraphSys, XVelGraph, x_vel_access, 500);
 
-fn y_vel_access(kine: &Kinematics) -> f32 {
-    kine.vel.y
+fn y_vel_access(kine: &Kinematics, frame_vel: Vector) -> f32 {
+    kine.vel.y - frame_vel.y
 }
 make_graphsys!(YVelGraphSys, YVelGraph, y_vel_access, 500);
 
-fn accel_access(kine: &Kinematics) -> Vector {
+fn accel_access(kine: &Kinematics, _frame_vel: Vector) -> Vector {
     kine.accel
 }
 make_graphsys!(AccelGraphSys, AccelGraph, accel_access, 25);
+
+fn accel_mag_access(kine: &Kinematics, _frame_vel: Vector) -> f32 {
+    kine.accel.norm()
+}
+make_graphsys!(AccelMagGraphSys, AccelMagGraph, accel_mag_access, 500);
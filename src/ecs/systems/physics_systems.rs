@@ -1,33 +1,43 @@
 use specs::prelude::*;
 
-use crate::ecs::components::{Draw, Kinematics, Mass, Position, Preview, Radius, Trail};
-use crate::ecs::resources::{NewPreview, DT};
-use crate::{new_body, Body, Point, Vector, G};
+use crate::ecs::components::{Aggregate, Anchored, Kinematics, Mass, Position, Preview, Radius};
+use crate::ecs::entities::{new_body, Body};
+use crate::ecs::resources::{
+    Boundary, GravityConstant, Integrator, MaxSpeed, MergeThreshold, NewPreview, Softening,
+    WorldSize, DT,
+};
+use crate::{Point, Vector};
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+
+#[cfg(any(test, feature = "parallel"))]
+use rayon::prelude::*;
 
 #[allow(unused_imports)]
 use microprofile::scope;
 
-// PhysicsSys and PreviewPhysicsSys are separate because PreviewPhysicsSys usually needs to be run
-// more times than PhysicsSys.
-// They use the same methods for the most part with only the toggle preview_only changed
-
-// The same closure is used with preview_only on or off, but the specs query is changed
+// PreviewPhysicsSys only steps previews, the main bodies are stepped by crate::physics.
+// Both use the same methods with a different set of bodies to update.
 
-pub struct PhysicsSys;
+pub struct PreviewPhysicsSys;
 
-impl<'a> System<'a> for PhysicsSys {
+impl<'a> System<'a> for PreviewPhysicsSys {
     type SystemData = (
         WriteStorage<'a, Position>,
         WriteStorage<'a, Kinematics>,
         ReadStorage<'a, Preview>,
         WriteStorage<'a, Radius>,
         WriteStorage<'a, Mass>,
-        Entities<'a>,
         Read<'a, DT>,
-        WriteStorage<'a, Draw>,
-        WriteStorage<'a, Trail>,
+        Read<'a, GravityConstant>,
+        Read<'a, Softening>,
+        Read<'a, Integrator>,
+        Read<'a, MaxSpeed>,
+        Read<'a, Boundary>,
+        Read<'a, WorldSize>,
+        Read<'a, MergeThreshold>,
+        Write<'a, NewPreview>,
+        Entities<'a>,
     );
 
     fn run(
@@ -36,232 +46,950 @@ impl<'a> System<'a> for PhysicsSys {
             mut positions,
             mut kinematics,
             previews,
-            mut radii,
-            mut masses,
-            entities,
+            radii,
+            masses,
             dt,
-            mut draws,
-            mut trails,
+            g,
+            softening,
+            integrator,
+            max_speed,
+            boundary,
+            world_size,
+            merge_threshold,
+            mut new_preview,
+            entities,
         ): Self::SystemData,
     ) {
-        integrate_positions(&mut positions, &kinematics, &previews, false, dt.0);
-        apply_gravity(
-            &positions,
+        if *integrator == Integrator::RK4 {
+            integrate_rk4(
+                &mut positions,
+                &mut kinematics,
+                &masses,
+                &entities,
+                previews.mask(),
+                dt.0,
+                GravitySettings {
+                    g: g.0,
+                    softening: softening.0,
+                },
+            );
+        } else {
+            integrate_positions(
+                &mut positions,
+                &kinematics,
+                previews.mask(),
+                dt.0,
+                *integrator,
+            );
+        }
+        apply_boundary(
+            &mut positions,
             &mut kinematics,
             &radii,
-            &masses,
-            &previews,
-            false,
+            previews.mask(),
+            *boundary,
+            world_size.0,
         );
-        integrate_kinematics(&mut kinematics, &previews, false, dt.0);
-        let (mut c_vec, mut delete_set) =
-            calc_collisions(&positions, &kinematics, &masses, &radii, &entities);
-
-        delete_set.drain().for_each(|e| {
-            entities.delete(e).expect("error deleting collided entity");
-        });
-
-        c_vec.drain(..).for_each(|body| {
-            entities
-                .build_entity()
-                .with(body.0, &mut positions)
-                .with(body.1, &mut kinematics)
-                .with(body.2, &mut masses)
-                .with(body.3, &mut draws)
-                .with(body.4, &mut radii)
-                .with(body.5, &mut trails)
-                .build();
-        });
-    }
-}
-
-pub struct PreviewPhysicsSys;
-
-impl<'a> System<'a> for PreviewPhysicsSys {
-    type SystemData = (
-        WriteStorage<'a, Position>,
-        WriteStorage<'a, Kinematics>,
-        ReadStorage<'a, Preview>,
-        WriteStorage<'a, Radius>,
-        WriteStorage<'a, Mass>,
-        Read<'a, DT>,
-        Write<'a, NewPreview>,
-    );
-
-    fn run(
-        &mut self,
-        (mut positions, mut kinematics, previews, radii, masses, dt, mut new_preview): Self::SystemData,
-    ) {
-        integrate_positions(&mut positions, &kinematics, &previews, true, dt.0);
         apply_gravity(
             &positions,
             &mut kinematics,
             &radii,
             &masses,
-            &previews,
-            true,
+            &entities,
+            previews.mask(),
+            GravitySettings {
+                g: g.0,
+                softening: softening.0,
+            },
         );
 
-        new_preview.0 = calc_preview_collisions(&positions, &radii, &previews);
+        new_preview.0 = calc_preview_collisions(&positions, &radii, &previews, merge_threshold.0);
 
-        integrate_kinematics(&mut kinematics, &previews, true, dt.0);
+        integrate_kinematics(
+            &mut kinematics,
+            previews.mask(),
+            dt.0,
+            *integrator,
+            max_speed.0,
+        );
     }
 }
 
-// verlet velocity integration
-fn integrate_positions(
+// The integrators and gravity only update the bodies in the bodies bitset, the main physics
+// passes the bodies that aren't frozen and the preview physics passes only the previews.
+
+pub fn integrate_positions(
     positions: &mut WriteStorage<'_, Position>,
     kinematics: &WriteStorage<'_, Kinematics>,
-    previews: &ReadStorage<'_, Preview>,
-    preview_only: bool,
+    bodies: &BitSet,
     dt: f32,
+    integrator: Integrator,
 ) {
     microprofile::scope!("Physics-System", "integrate_positions");
-    let int_closure = |(pos, kinematics): (&mut Position, &Kinematics)| {
-        pos.0 += kinematics.vel * dt + (kinematics.accel / 2.0) * dt.powi(2);
+    (positions, kinematics, bodies)
+        .par_join()
+        .for_each(|(pos, kinematics, _)| match integrator {
+            Integrator::Euler | Integrator::Leapfrog => pos.0 += kinematics.vel * dt,
+            Integrator::VelocityVerlet => {
+                pos.0 += kinematics.vel * dt + (kinematics.accel / 2.0) * dt.powi(2)
+            }
+            // stepped by integrate_rk4 instead
+            Integrator::RK4 => {}
+        });
+}
+
+// Steps both the positions and velocities of the bodies with 4th order Runge-Kutta. Every stage
+// recalculates gravity at the trial positions of all the bodies, bodies that aren't being stepped
+// still attract from where they are.
+pub fn integrate_rk4(
+    positions: &mut WriteStorage<'_, Position>,
+    kinematics: &mut WriteStorage<'_, Kinematics>,
+    masses: &WriteStorage<'_, Mass>,
+    entities: &Entities,
+    bodies: &BitSet,
+    dt: f32,
+    settings: GravitySettings,
+) {
+    microprofile::scope!("Physics-System", "integrate_rk4");
+    let GravitySettings { g, softening } = settings;
+    let (moving, start_pos, start_vel): (Vec<Entity>, Vec<Point>, Vec<Vector>) = {
+        let states: Vec<(Entity, Point, Vector)> = (entities, &*positions, &*kinematics, bodies)
+            .join()
+            .map(|(entity, pos, kine, _)| (entity, pos.0, kine.vel))
+            .collect();
+        (
+            states.iter().map(|state| state.0).collect(),
+            states.iter().map(|state| state.1).collect(),
+            states.iter().map(|state| state.2).collect(),
+        )
+    };
+    let index: HashMap<Entity, usize> = moving
+        .iter()
+        .enumerate()
+        .map(|(i, entity)| (*entity, i))
+        .collect();
+    // the attracting bodies, with the index of their trial position if they're moving
+    let attractors: Vec<(Option<usize>, Point, f32)> = (entities, &*positions, masses)
+        .join()
+        .map(|(entity, pos, mass)| (index.get(&entity).copied(), pos.0, mass.0))
+        .collect();
+
+    // same as apply_gravity, with the moving bodies at trial_pos
+    let accels = |trial_pos: &[Point]| -> Vec<Vector> {
+        trial_pos
+            .iter()
+            .map(|pos| {
+                attractors.iter().fold(
+                    Vector::new(0.0, 0.0),
+                    |grav_accel_acc, (i, other_pos, other_mass)| {
+                        let other_pos = i.map_or(*other_pos, |i| trial_pos[i]);
+                        if other_pos != *pos {
+                            grav_accel_acc
+                                + gravity_accel(other_pos - pos, *other_mass, g, softening)
+                        } else {
+                            grav_accel_acc
+                        }
+                    },
+                )
+            })
+            .collect()
+    };
+    // the start of the step moved along derivs for h
+    let offset_pos = |derivs: &[Vector], h: f32| -> Vec<Point> {
+        start_pos
+            .iter()
+            .zip(derivs)
+            .map(|(pos, deriv)| pos + deriv * h)
+            .collect()
+    };
+    let offset_vel = |derivs: &[Vector], h: f32| -> Vec<Vector> {
+        start_vel
+            .iter()
+            .zip(derivs)
+            .map(|(vel, deriv)| vel + deriv * h)
+            .collect()
     };
 
-    if !preview_only {
-        (positions, kinematics).par_join().for_each(int_closure);
-    } else {
-        (positions, kinematics, previews)
-            .join() // not parallel because there's probably only one preview
-            .for_each(|(pos, kine, _)| {
-                int_closure((pos, kine));
-            });
+    let (k1_vel, k1_accel) = (start_vel.clone(), accels(&start_pos));
+    let (k2_vel, k2_accel) = (
+        offset_vel(&k1_accel, dt / 2.0),
+        accels(&offset_pos(&k1_vel, dt / 2.0)),
+    );
+    let (k3_vel, k3_accel) = (
+        offset_vel(&k2_accel, dt / 2.0),
+        accels(&offset_pos(&k2_vel, dt / 2.0)),
+    );
+    let (k4_vel, k4_accel) = (offset_vel(&k3_accel, dt), accels(&offset_pos(&k3_vel, dt)));
+
+    let weighted = |k1: &[Vector], k2: &[Vector], k3: &[Vector], k4: &[Vector]| -> Vec<Vector> {
+        (0..k1.len())
+            .map(|i| (k1[i] + k2[i] * 2.0 + k3[i] * 2.0 + k4[i]) / 6.0)
+            .collect()
+    };
+    let new_pos = offset_pos(&weighted(&k1_vel, &k2_vel, &k3_vel, &k4_vel), dt);
+    let new_vel = offset_vel(&weighted(&k1_accel, &k2_accel, &k3_accel, &k4_accel), dt);
+
+    moving.iter().enumerate().for_each(|(i, entity)| {
+        positions.get_mut(*entity).unwrap().0 = new_pos[i];
+        kinematics.get_mut(*entity).unwrap().vel = new_vel[i];
+    });
+}
+
+// Keeps bodies inside the box from the origin to size. Wrapping only looks at the center of a body,
+// reflecting keeps the whole body inside as long as it fits.
+pub fn apply_boundary(
+    positions: &mut WriteStorage<'_, Position>,
+    kinematics: &mut WriteStorage<'_, Kinematics>,
+    radii: &WriteStorage<'_, Radius>,
+    bodies: &BitSet,
+    boundary: Boundary,
+    size: Vector,
+) {
+    if boundary == Boundary::None || size.x <= 0.0 || size.y <= 0.0 {
+        return;
     }
+
+    (positions, kinematics, radii, bodies).par_join().for_each(
+        |(pos, kine, rad, _)| match boundary {
+            Boundary::Wrap => {
+                pos.0.x = pos.0.x.rem_euclid(size.x);
+                pos.0.y = pos.0.y.rem_euclid(size.y);
+            }
+            Boundary::Reflect => {
+                for axis in 0..2 {
+                    let low = rad.0.min(size[axis] / 2.0);
+                    let high = size[axis] - low;
+                    if pos.0[axis] < low {
+                        pos.0[axis] = low;
+                        kine.vel[axis] = kine.vel[axis].abs();
+                    } else if pos.0[axis] > high {
+                        pos.0[axis] = high;
+                        kine.vel[axis] = -kine.vel[axis].abs();
+                    }
+                }
+            }
+            Boundary::None => {}
+        },
+    );
+}
+
+// GravityConstant and Softening, which every way of calculating gravity needs together
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GravitySettings {
+    pub g: f32,
+    pub softening: f32,
 }
 
-fn apply_gravity(
+pub fn apply_gravity(
     positions: &WriteStorage<'_, Position>,
     kinematics: &mut WriteStorage<'_, Kinematics>,
     radii: &WriteStorage<'_, Radius>,
     masses: &WriteStorage<'_, Mass>,
-    previews: &ReadStorage<'_, Preview>,
-    preview_only: bool,
+    entities: &Entities,
+    bodies: &BitSet,
+    settings: GravitySettings,
 ) {
     // for each body, sum the accelerations of gravity from every other body and add it
     microprofile::scope!("Physics-System", "apply_gravity");
-    let grav_closure = |(current_pos, kinematics, _): (&Position, &mut Kinematics, &Radius)| {
-        kinematics.accel = (positions, masses).join().fold(
-            Vector::new(0.0, 0.0),
-            |grav_accel_acc, (other_pos, other_mass)| {
-                if current_pos != other_pos {
-                    let dist_vec = other_pos.0 - current_pos.0;
-                    let dist_mag_sqr = dist_vec.norm_squared();
-                    let dist_mag = dist_mag_sqr.powf(0.5);
-                    let dist_comp = dist_vec / dist_mag;
+    let targets: Vec<(Entity, Point)> = (entities, positions, &*kinematics, radii, bodies)
+        .join()
+        .map(|(e, pos, _, _, _)| (e, pos.0))
+        .collect();
+    let others: Vec<(Point, f32)> = (positions, masses)
+        .join()
+        .map(|(pos, mass)| (pos.0, mass.0))
+        .collect();
+
+    #[cfg(feature = "parallel")]
+    let accels = gravity_accels_parallel(&targets, &others, settings);
+    #[cfg(not(feature = "parallel"))]
+    let accels = gravity_accels_serial(&targets, &others, settings);
+
+    accels.into_iter().for_each(|(e, accel)| {
+        if let Some(kine) = kinematics.get_mut(e) {
+            kine.accel = accel;
+        }
+    });
+}
 
-                    let grav_accel_mag = other_mass.0 / dist_mag_sqr * G;
-                    let grav_accel: Vector = dist_comp * grav_accel_mag;
+#[cfg(any(test, not(feature = "parallel")))]
+fn gravity_accels_serial(
+    targets: &[(Entity, Point)],
+    others: &[(Point, f32)],
+    settings: GravitySettings,
+) -> Vec<(Entity, Vector)> {
+    targets
+        .iter()
+        .map(|&(e, pos)| (e, pull_on(pos, others, settings)))
+        .collect()
+}
 
-                    grav_accel_acc + grav_accel
-                } else {
-                    grav_accel_acc
-                }
-            },
-        );
-    };
+// each body's sum only reads the others so they can all be done at once, and each is summed in the
+// same order as the serial version so the results are identical
+#[cfg(any(test, feature = "parallel"))]
+fn gravity_accels_parallel(
+    targets: &[(Entity, Point)],
+    others: &[(Point, f32)],
+    settings: GravitySettings,
+) -> Vec<(Entity, Vector)> {
+    targets
+        .par_iter()
+        .map(|&(e, pos)| (e, pull_on(pos, others, settings)))
+        .collect()
+}
 
-    if !preview_only {
-        (positions, kinematics, radii)
-            .par_join()
-            .for_each(grav_closure);
-    } else {
-        (positions, kinematics, radii, previews)
-            .join()
-            .for_each(|(p, k, r, _)| grav_closure((p, k, r)));
-    }
+// the pull of every other body on a body at pos, a body doesn't pull on itself
+fn pull_on(pos: Point, others: &[(Point, f32)], settings: GravitySettings) -> Vector {
+    others.iter().fold(
+        Vector::new(0.0, 0.0),
+        |grav_accel_acc, &(other_pos, other_mass)| {
+            if pos != other_pos {
+                let dist_vec = other_pos - pos;
+                grav_accel_acc + gravity_accel(dist_vec, other_mass, settings.g, settings.softening)
+            } else {
+                grav_accel_acc
+            }
+        },
+    )
+}
+
+// The pull towards a body of other_mass that's dist_vec away. The distance is softened so the pull
+// stays finite as it goes to 0.
+pub fn gravity_accel(dist_vec: Vector, other_mass: f32, g: f32, softening: f32) -> Vector {
+    let softened_sqr = dist_vec.norm_squared() + softening.powi(2);
+    let softened_mag = softened_sqr.powf(0.5);
+    let dist_comp = dist_vec / softened_mag;
+
+    let grav_accel_mag = other_mass / softened_sqr * g;
+    dist_comp * grav_accel_mag
 }
 
 // separate from integrate_positions because verlet velocity integration wants acceleration to be
 // calculated between integrating positions and applying kinematics
-fn integrate_kinematics(
+pub fn integrate_kinematics(
     kinematics: &mut WriteStorage<'_, Kinematics>,
-    previews: &ReadStorage<'_, Preview>,
-    preview_only: bool,
+    bodies: &BitSet,
     dt: f32,
+    integrator: Integrator,
+    max_speed: Option<f32>,
 ) {
     microprofile::scope!("Physics-System", "integrate_kinematics");
-    let kine_int_closure = |kinematics: &mut Kinematics| {
-        *kinematics.vel = *(kinematics.vel + (kinematics.accel + kinematics.past_accel) / 2.0 * dt);
+    (kinematics, bodies).par_join().for_each(|(kinematics, _)| {
+        // past_accel is the acceleration at the start of the step
+        let accel = match integrator {
+            Integrator::Euler => kinematics.past_accel,
+            Integrator::Leapfrog => kinematics.accel,
+            Integrator::VelocityVerlet => (kinematics.accel + kinematics.past_accel) / 2.0,
+            // integrate_rk4 already stepped the velocity
+            Integrator::RK4 => Vector::new(0.0, 0.0),
+        };
+        *kinematics.vel = *(kinematics.vel + accel * dt);
         kinematics.past_accel = kinematics.accel;
-    };
 
-    if !preview_only {
-        (kinematics).par_join().for_each(kine_int_closure);
-    } else {
-        (kinematics, previews).join().for_each(|(kine, _)| {
-            kine_int_closure(kine);
-        });
+        // not momentum conserving, see resources::MaxSpeed
+        if let Some(max_speed) = max_speed {
+            let speed = kinematics.vel.norm();
+            if speed > max_speed {
+                kinematics.vel *= max_speed / speed;
+            }
+        }
+    });
+}
+
+// Damps the relative velocity of every pair of bodies by coefficient * (m1 + m2) / d^4 per step,
+// the caller folds the strength, |g| and the timestep into coefficient. The opposite impulses are
+// weighted by the reduced mass so momentum is conserved. Returns the kinetic energy lost.
+pub fn apply_inspiral(
+    positions: &WriteStorage<'_, Position>,
+    kinematics: &mut WriteStorage<'_, Kinematics>,
+    masses: &WriteStorage<'_, Mass>,
+    entities: &Entities,
+    bodies: &BitSet,
+    coefficient: f32,
+) -> f32 {
+    microprofile::scope!("Physics-System", "apply_inspiral");
+    let pairs: Vec<(Entity, Point, Vector, f32)> =
+        (positions, &*kinematics, masses, entities, bodies)
+            .join()
+            .filter(|(_, _, mass, _, _)| mass.0 > 0.0)
+            .map(|(pos, kine, mass, e, _)| (e, pos.0, kine.vel, mass.0))
+            .collect();
+
+    let mut deltas = vec![Vector::new(0.0, 0.0); pairs.len()];
+    let mut dissipated = 0.0;
+
+    for i in 0..pairs.len() {
+        for j in i + 1..pairs.len() {
+            let (_, p1, v1, m1) = pairs[i];
+            let (_, p2, v2, m2) = pairs[j];
+
+            let dist_sqr = (p2 - p1).norm_squared();
+            if dist_sqr == 0.0 {
+                continue;
+            }
+
+            let damping = (coefficient * (m1 + m2) / dist_sqr.powi(2)).min(1.0);
+            let reduced_mass = m1 * m2 / (m1 + m2);
+            let rel_vel = v2 - v1;
+
+            deltas[i] += rel_vel * damping * reduced_mass / m1;
+            deltas[j] -= rel_vel * damping * reduced_mass / m2;
+            dissipated +=
+                0.5 * reduced_mass * rel_vel.norm_squared() * (1.0 - (1.0 - damping).powi(2));
+        }
     }
+
+    pairs.iter().zip(deltas).for_each(|((e, _, _, _), delta)| {
+        if let Some(kine) = kinematics.get_mut(*e) {
+            kine.vel += delta;
+        }
+    });
+
+    dissipated
+}
+
+// MergeThreshold and StickThreshold, how close bodies have to get to merge and how slowly they
+// have to hit to stick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MergeSettings {
+    pub merge_threshold: f32,
+    pub stick_threshold: Option<f32>,
 }
 
-fn calc_collisions(
+pub fn calc_collisions(
     positions: &WriteStorage<'_, Position>,
     kinematics: &WriteStorage<'_, Kinematics>,
     masses: &WriteStorage<'_, Mass>,
     radii: &WriteStorage<'_, Radius>,
+    anchors: &ReadStorage<'_, Anchored>,
     entities: &Entities,
-) -> (Vec<Body>, HashSet<Entity>) {
+    settings: MergeSettings,
+) -> (
+    Vec<(Body, bool)>,
+    Vec<Entity>,
+    Vec<(Entity, Entity, f32)>,
+    Vec<(Entity, Entity)>,
+) {
     microprofile::scope!("Physics-System", "calc_collisions");
-    let mut create_vec: Vec<Body> = Vec::new();
+    let MergeSettings {
+        merge_threshold,
+        stick_threshold,
+    } = settings;
+    // the merged bodies, and whether they're anchored
+    let mut create_vec: Vec<(Body, bool)> = Vec::new();
     let mut delete_set: HashSet<Entity> = HashSet::new();
+    // each merged pair with the kinetic energy lost
+    let mut merges: Vec<(Entity, Entity, f32)> = Vec::new();
+    // pairs that hit too fast to stick
+    let mut bounces: Vec<(Entity, Entity)> = Vec::new();
+
+    // The joins go in order of entity id so the pairs are always merged in the same order. The
+    // deleted bodies are returned sorted by id as well, the order they're deleted in decides which
+    // ids the merged bodies are given, so a run is reproducible from the same bodies and DT.
+    let mut pairs: Vec<(Entity, Entity)> = Vec::new();
+    (positions, radii, masses, entities)
+        .join()
+        .for_each(|(pos1, r1, _, e1)| {
+            (positions, radii, masses, entities)
+                .join()
+                .for_each(|(pos2, r2, _, e2)| {
+                    if e1.id() < e2.id()
+                        && pos1.dist_squared(*pos2) <= ((r1.0 + r2.0) * merge_threshold).powi(2)
+                    {
+                        pairs.push((e1, e2));
+                    }
+                });
+        });
+
+    pairs.into_iter().for_each(|(e1, e2)| {
+        if delete_set.contains(&e1) || delete_set.contains(&e2) {
+            return;
+        }
+        let body = |e: Entity| match (
+            positions.get(e),
+            radii.get(e),
+            masses.get(e),
+            kinematics.get(e),
+        ) {
+            (Some(pos), Some(rad), Some(mass), Some(kine)) => Some((pos, rad, mass, kine)),
+            _ => None,
+        };
+        let ((pos1, r1, m1, k1), (pos2, r2, m2, k2)) = match (body(e1), body(e2)) {
+            (Some(body1), Some(body2)) => (body1, body2),
+            _ => return,
+        };
+
+        if let Some(threshold) = stick_threshold {
+            if (k1.vel - k2.vel).norm() >= threshold {
+                bounces.push((e1, e2));
+                return;
+            }
+        }
+
+        delete_set.insert(e1);
+        delete_set.insert(e2);
+
+        // completely inelastic collisions
+        let p1 = k1.vel * m1.0;
+        let p2 = k2.vel * m2.0;
+        let ptotal = p1 + p2;
+
+        let mtotal = m1.0 + m2.0;
+
+        // an anchor absorbs the other body without moving
+        let (anchored1, anchored2) = (anchors.contains(e1), anchors.contains(e2));
+        let new_vel = if anchored1 || anchored2 {
+            Vector::new(0.0, 0.0)
+        } else {
+            ptotal / mtotal
+        };
+
+        // new radius calculated by summing 3D volumes
+        let new_rad = (r1.0.powi(3) + r2.0.powi(3)).powf(1. / 3.);
+
+        // new positions calculated my weighted average position by mass
+        let new_pos = match (anchored1, anchored2) {
+            (true, false) => pos1.0,
+            (false, true) => pos2.0,
+            _ => {
+                let weighted_p1 = pos1.0 * m1.0;
+                let weighted_p2 = pos2.0 * m2.0;
+                let sum_weighted =
+                    Point::new(weighted_p1.x + weighted_p2.x, weighted_p1.y + weighted_p2.y);
+                sum_weighted / mtotal
+            }
+        };
+
+        // kinetic energy lost to the inelastic collision
+        let ke_before = 0.5 * m1.0 * k1.vel.norm_squared() + 0.5 * m2.0 * k2.vel.norm_squared();
+        let ke_after = 0.5 * mtotal * new_vel.norm_squared();
+
+        merges.push((e1, e2, ke_before - ke_after));
+
+        // The merged body carries on with the weighted accelerations too, the verlet step
+        // averages with past_accel and starting it at 0 would only give the merged body half
+        // of its pull while everything else feels all of theirs, so momentum wouldn't be
+        // conserved over the next step.
+        let mut merged = new_body(new_pos, new_vel, mtotal, new_rad);
+        (merged.1).accel = (k1.accel * m1.0 + k2.accel * m2.0) / mtotal;
+        (merged.1).past_accel = (k1.past_accel * m1.0 + k2.past_accel * m2.0) / mtotal;
+
+        create_vec.push((merged, anchored1 || anchored2));
+    });
+
+    let mut deleted: Vec<Entity> = delete_set.into_iter().collect();
+    deleted.sort_by_key(|e| e.id());
+
+    (create_vec, deleted, merges, bounces)
+}
 
-    (positions, radii, masses, kinematics, entities)
+// pairs of touching bodies which aren't already in the same aggregate
+pub fn find_contacts(
+    positions: &WriteStorage<'_, Position>,
+    radii: &WriteStorage<'_, Radius>,
+    masses: &WriteStorage<'_, Mass>,
+    aggregates: &WriteStorage<'_, Aggregate>,
+    entities: &Entities,
+) -> Vec<(Entity, Entity)> {
+    microprofile::scope!("Physics-System", "find_contacts");
+    let mut contacts = Vec::new();
+
+    (positions, radii, masses, entities)
         .join()
-        .for_each(|(pos1, r1, m1, k1, e1)| {
-            (positions, radii, masses, kinematics, entities)
+        .for_each(|(pos1, r1, _, e1)| {
+            (positions, radii, masses, entities)
                 .join()
-                .for_each(|(pos2, r2, m2, k2, e2)| {
-                    if e1 != e2
+                .for_each(|(pos2, r2, _, e2)| {
+                    let same_aggregate = match (aggregates.get(e1), aggregates.get(e2)) {
+                        (Some(a1), Some(a2)) => a1.group == a2.group,
+                        _ => false,
+                    };
+
+                    if e1.id() < e2.id()
+                        && !same_aggregate
                         && pos1.dist_squared(*pos2) <= (r1.0 + r2.0).powi(2)
-                        && !delete_set.contains(&e1)
-                        && !delete_set.contains(&e2)
                     {
-                        delete_set.insert(e1);
-                        delete_set.insert(e2);
+                        contacts.push((e1, e2));
+                    }
+                });
+        });
 
-                        // completely inelastic collisions
-                        let p1 = k1.vel * m1.0;
-                        let p2 = k2.vel * m2.0;
-                        let ptotal = p1 + p2;
+    contacts
+}
 
-                        let mtotal = m1.0 + m2.0;
+// Resolves an elastic collision between two touching bodies given as (position, velocity, mass,
+// radius) along the line between their centers. Returns each body's new position and velocity,
+// they're pushed apart in inverse proportion to their masses so they no longer overlap. Anchored
+// bodies are given an infinite mass so they don't move.
+pub fn bounce(
+    (pos1, vel1, mass1, rad1): (Point, Vector, f32, f32),
+    (pos2, vel2, mass2, rad2): (Point, Vector, f32, f32),
+) -> ((Point, Vector), (Point, Vector)) {
+    let (share1, share2) = match bounce_shares(mass1, mass2) {
+        Some(shares) => shares,
+        None => return ((pos1, vel1), (pos2, vel2)),
+    };
 
-                        let new_vel = ptotal / mtotal;
+    let dist_vec = pos2 - pos1;
+    let dist = dist_vec.norm();
+    // bodies in the same spot are split along x
+    let normal = if dist == 0.0 {
+        Vector::new(1.0, 0.0)
+    } else {
+        dist_vec / dist
+    };
 
-                        // new radius calculated by summing 3D volumes
-                        let new_rad = (r1.0.powi(3) + r2.0.powi(3)).powf(1. / 3.);
+    // only bodies moving towards each other exchange momentum
+    let approach_speed = (vel1 - vel2).dot(&normal);
+    let (vel1, vel2) = if approach_speed > 0.0 {
+        let exchange = normal * (2.0 * approach_speed);
+        (vel1 - exchange * share1, vel2 + exchange * share2)
+    } else {
+        (vel1, vel2)
+    };
 
-                        // new positions calculated my weighted average position by mass
-                        let new_pos = {
-                            let weighted_p1 = pos1.0 * m1.0;
-                            let weighted_p2 = pos2.0 * m2.0;
-                            let sum_weighted = Point::new(
-                                weighted_p1.x + weighted_p2.x,
-                                weighted_p1.y + weighted_p2.y,
-                            );
-                            sum_weighted / mtotal
-                        };
+    let overlap = (rad1 + rad2 - dist).max(0.0);
+    let pos1 = pos1 - normal * (overlap * share1);
+    let pos2 = pos2 + normal * (overlap * share2);
 
-                        create_vec.push(new_body(new_pos, new_vel, mtotal, new_rad));
-                    }
-                });
+    ((pos1, vel1), (pos2, vel2))
+}
+
+// How much of the velocity change and separation of a bounce each body takes, the other body's
+// share of the total mass. A massless body or one hitting an infinite mass takes all of it, None if
+// both are massless or both are infinite.
+fn bounce_shares(mass1: f32, mass2: f32) -> Option<(f32, f32)> {
+    let total_mass = mass1 + mass2;
+    match (mass1.is_infinite(), mass2.is_infinite()) {
+        (true, true) => None,
+        (true, false) => Some((0.0, 1.0)),
+        (false, true) => Some((1.0, 0.0)),
+        _ if total_mass == 0.0 => None,
+        _ => Some((mass2 / total_mass, mass1 / total_mass)),
+    }
+}
+
+struct RigidGroup {
+    mass: f32,
+    weighted_pos: Vector,
+    momentum: Vector,
+    weighted_offset: Vector,
+    angular_momentum: f32,
+    inertia: f32,
+    angle: f32,
+}
+
+impl RigidGroup {
+    fn new(angle: f32) -> Self {
+        RigidGroup {
+            mass: 0.0,
+            weighted_pos: Vector::new(0.0, 0.0),
+            momentum: Vector::new(0.0, 0.0),
+            weighted_offset: Vector::new(0.0, 0.0),
+            angular_momentum: 0.0,
+            inertia: 0.0,
+            angle,
+        }
+    }
+}
+
+fn rotate(v: Vector, angle: f32) -> Vector {
+    let (sin, cos) = angle.sin_cos();
+    Vector::new(cos * v.x - sin * v.y, sin * v.x + cos * v.y)
+}
+
+// Makes every aggregate move as a rigid body. The members are integrated separately like any
+// other body, then each group's momentum and angular momentum about its center of mass are
+// summed and the members are put back in formation moving with them.
+pub fn integrate_aggregates(
+    positions: &mut WriteStorage<'_, Position>,
+    kinematics: &mut WriteStorage<'_, Kinematics>,
+    masses: &WriteStorage<'_, Mass>,
+    aggregates: &mut WriteStorage<'_, Aggregate>,
+    dt: f32,
+) {
+    microprofile::scope!("Physics-System", "integrate_aggregates");
+    let mut groups: HashMap<u64, RigidGroup> = HashMap::new();
+
+    (&*positions, &*kinematics, masses, &*aggregates)
+        .join()
+        .for_each(|(pos, kine, mass, aggregate)| {
+            let group = groups
+                .entry(aggregate.group)
+                .or_insert_with(|| RigidGroup::new(aggregate.angle));
+            group.mass += mass.0;
+            group.weighted_pos += pos.0.coords * mass.0;
+            group.momentum += kine.vel * mass.0;
+            group.weighted_offset += aggregate.offset * mass.0;
         });
 
-    (create_vec, delete_set)
+    // members may have been removed since the offsets were calculated so they're recentered
+    groups.retain(|_, group| group.mass != 0.0);
+
+    (&*positions, &*kinematics, masses, &*aggregates)
+        .join()
+        .for_each(|(pos, kine, mass, aggregate)| {
+            if let Some(group) = groups.get_mut(&aggregate.group) {
+                let r = pos.0.coords - group.weighted_pos / group.mass;
+                let v = kine.vel - group.momentum / group.mass;
+                let offset = aggregate.offset - group.weighted_offset / group.mass;
+                group.angular_momentum += mass.0 * (r.x * v.y - r.y * v.x);
+                group.inertia += mass.0 * offset.norm_squared();
+            }
+        });
+
+    (positions, kinematics, masses, aggregates)
+        .join()
+        .for_each(|(pos, kine, _, aggregate)| {
+            if let Some(group) = groups.get(&aggregate.group) {
+                let angular_vel = if group.inertia != 0.0 {
+                    group.angular_momentum / group.inertia
+                } else {
+                    0.0
+                };
+                let angle = group.angle + angular_vel * dt;
+
+                let offset = aggregate.offset - group.weighted_offset / group.mass;
+                let r = rotate(offset, angle);
+                let new_vel = group.momentum / group.mass + Vector::new(-r.y, r.x) * angular_vel;
+
+                pos.0 = Point::from(group.weighted_pos / group.mass + r);
+                kine.vel = new_vel;
+                aggregate.angle = angle;
+            }
+        });
 }
 
-// previews don't affect anything so the collision method is much simpler and separate
+// Splits a body into the fewest equal fragments that are all below max_mass, spread evenly on a
+// ring so they don't overlap. The fragments fly outward at the escape speed of the whole body so
+// they don't immediately fall back together, the outward velocities cancel out so mass and
+// momentum are conserved.
+pub fn fragment(body: Body, max_mass: f32, g: f32) -> Vec<Body> {
+    let (pos, kinematics, mass, _, rad, _) = body;
+    if max_mass <= 0.0 || mass.0 <= max_mass {
+        return vec![body];
+    }
+
+    let pieces = (mass.0 / max_mass).floor() as usize + 1;
+    let piece_mass = mass.0 / pieces as f32;
+    // the volume is split evenly, like the radius of merged bodies is calculated
+    let piece_rad = rad.0 * (1.0 / pieces as f32).powf(1. / 3.);
+
+    // far enough apart that neighbouring fragments aren't touching
+    let angle_step = 2.0 * std::f32::consts::PI / pieces as f32;
+    let ring_rad = 1.1 * piece_rad / (angle_step / 2.0).sin();
+    let escape_speed = (2.0 * g.abs() * mass.0 / ring_rad).sqrt();
+
+    (0..pieces)
+        .map(|i| {
+            let angle = angle_step * i as f32;
+            let dir = Vector::new(angle.cos(), angle.sin());
+            new_body(
+                pos.0 + dir * ring_rad,
+                kinematics.vel + dir * escape_speed,
+                piece_mass,
+                piece_rad,
+            )
+        })
+        .collect()
+}
+
+// Previews don't affect anything so the collision method is much simpler and separate. It uses
+// the same distance as calc_collisions so the preview only warns about hits that would merge.
 fn calc_preview_collisions(
     positions: &WriteStorage<'_, Position>,
     radii: &WriteStorage<'_, Radius>,
     previews: &ReadStorage<'_, Preview>,
+    merge_threshold: f32,
 ) -> bool {
     (positions, radii, previews).join().any(|(pos1, rad1, _)| {
-        (positions, radii)
-            .join()
-            .any(|(pos2, rad2)| pos1 != pos2 && pos1.dist(pos2.0) <= rad1.0 + rad2.0)
+        (positions, radii).join().any(|(pos2, rad2)| {
+            pos1 != pos2
+                && pos1.dist_squared(*pos2) <= ((rad1.0 + rad2.0) * merge_threshold).powi(2)
+        })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::entities::{create_body, create_preview, new_preview};
+    use crate::ecs::graphics::Rect;
+    use crate::ecs::world::new_world;
+    use crate::presets::random_bodies;
+
+    const MERGE_SETTINGS: MergeSettings = MergeSettings {
+        merge_threshold: 1.0,
+        stick_threshold: None,
+    };
+
+    // the bodies that bodies merge into
+    fn merged(bodies: Vec<Body>, settings: MergeSettings) -> Vec<Body> {
+        let mut world = new_world();
+        bodies.into_iter().for_each(|body| {
+            create_body(&mut world, body);
+        });
+
+        let (positions, kinematics, masses, radii, anchors, entities): (
+            WriteStorage<Position>,
+            WriteStorage<Kinematics>,
+            WriteStorage<Mass>,
+            WriteStorage<Radius>,
+            ReadStorage<Anchored>,
+            Entities,
+        ) = world.system_data();
+        let (create_vec, _, _, _) = calc_collisions(
+            &positions,
+            &kinematics,
+            &masses,
+            &radii,
+            &anchors,
+            &entities,
+            settings,
+        );
+
+        create_vec.into_iter().map(|(body, _)| body).collect()
+    }
+
+    fn momentum(bodies: &[(Vector, f32)]) -> Vector {
+        bodies
+            .iter()
+            .fold(Vector::new(0.0, 0.0), |acc, (vel, mass)| acc + vel * *mass)
+    }
+
+    fn kinetic_energy(bodies: &[(Vector, f32)]) -> f32 {
+        bodies
+            .iter()
+            .map(|(vel, mass)| 0.5 * mass * vel.norm_squared())
+            .sum()
+    }
+
+    // the parallel feature has to give exactly the same result as the serial default
+    #[test]
+    fn parallel_gravity_matches_serial() {
+        let mut world = new_world();
+        random_bodies(50, 3, Rect::new(0.0, 0.0, 100.0, 100.0))
+            .into_iter()
+            .for_each(|body| {
+                create_body(&mut world, body);
+            });
+        let settings = GravitySettings {
+            g: 1.2,
+            softening: 1.0,
+        };
+
+        let (entities, positions, masses): (Entities, ReadStorage<Position>, ReadStorage<Mass>) =
+            world.system_data();
+        let targets: Vec<(Entity, Point)> = (&entities, &positions)
+            .join()
+            .map(|(e, pos)| (e, pos.0))
+            .collect();
+        let others: Vec<(Point, f32)> = (&positions, &masses)
+            .join()
+            .map(|(pos, mass)| (pos.0, mass.0))
+            .collect();
+
+        let serial = gravity_accels_serial(&targets, &others, settings);
+        assert_eq!(serial.len(), 50);
+        assert!(serial.iter().all(|(_, accel)| accel.norm() > 0.0));
+        assert_eq!(gravity_accels_parallel(&targets, &others, settings), serial);
+    }
+
+    #[test]
+    fn merging_conserves_momentum() {
+        let body1 = new_body([0.0, 0.0], [1.0, 0.5], 3.0, 1.0);
+        let body2 = new_body([1.5, 0.0], [-2.0, 0.0], 1.0, 1.0);
+        let before = (body1.1).vel * (body1.2).0 + (body2.1).vel * (body2.2).0;
+
+        let merged = merged(vec![body1, body2], MERGE_SETTINGS);
+        assert_eq!(merged.len(), 1);
+        let (pos, kine, mass, _, _, _) = &merged[0];
+        assert!((kine.vel * mass.0 - before).norm() < 1e-5);
+        assert!((mass.0 - 4.0).abs() < 1e-5);
+        // at the center of mass
+        assert!((pos.0 - Point::new(0.375, 0.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn merges_at_exactly_the_threshold() {
+        let settings = MergeSettings {
+            merge_threshold: 1.5,
+            ..MERGE_SETTINGS
+        };
+        let pair = |dist: f32| {
+            vec![
+                new_body([0.0, 0.0], [0.0, 0.0], 1.0, 1.0),
+                new_body([dist, 0.0], [0.0, 0.0], 1.0, 1.0),
+            ]
+        };
+
+        assert_eq!(merged(pair(3.0), settings).len(), 1);
+        assert_eq!(merged(pair(3.001), settings).len(), 0);
+    }
+
+    #[test]
+    fn previews_collide_at_the_merge_threshold() {
+        let mut world = new_world();
+        create_body(&mut world, new_body([0.0, 0.0], [0.0, 0.0], 1.0, 1.0));
+        create_preview(&mut world, new_preview([3.0, 0.0], [0.0, 0.0], 1.0));
+
+        let (positions, radii, previews): (
+            WriteStorage<Position>,
+            WriteStorage<Radius>,
+            ReadStorage<Preview>,
+        ) = world.system_data();
+        assert!(calc_preview_collisions(&positions, &radii, &previews, 1.5));
+        assert!(!calc_preview_collisions(&positions, &radii, &previews, 1.0));
+    }
+
+    #[test]
+    fn bounce_conserves_momentum_and_energy() {
+        let body1 = (Point::new(0.0, 0.0), Vector::new(1.0, 0.0), 2.0, 1.0);
+        let body2 = (Point::new(1.5, 0.0), Vector::new(-1.0, 0.0), 2.0, 1.0);
+        let ((_, vel1), (_, vel2)) = bounce(body1, body2);
+
+        let before = [(body1.1, body1.2), (body2.1, body2.2)];
+        let after = [(vel1, body1.2), (vel2, body2.2)];
+        assert!((momentum(&before) - momentum(&after)).norm() < 1e-5);
+        assert!((kinetic_energy(&before) - kinetic_energy(&after)).abs() < 1e-5);
+        // equal masses head on swap velocities
+        assert!((vel1 - Vector::new(-1.0, 0.0)).norm() < 1e-5);
+        assert!((vel2 - Vector::new(1.0, 0.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn massless_body_bounces_off() {
+        let body1 = (Point::new(0.0, 0.0), Vector::new(1.0, 0.0), 0.0, 1.0);
+        let body2 = (Point::new(1.5, 0.0), Vector::new(0.0, 0.0), 2.0, 1.0);
+        let ((pos1, vel1), (pos2, vel2)) = bounce(body1, body2);
+
+        assert!((vel1 - Vector::new(-1.0, 0.0)).norm() < 1e-5);
+        assert_eq!(vel2, body2.1);
+        assert!(pos1.x.is_finite() && pos1.y.is_finite());
+        assert_eq!(pos2, body2.0);
+    }
+
+    #[test]
+    fn anchors_dont_bounce() {
+        let anchor = (
+            Point::new(0.0, 0.0),
+            Vector::new(0.0, 0.0),
+            std::f32::INFINITY,
+            1.0,
+        );
+        let body = (Point::new(1.5, 0.0), Vector::new(-1.0, 0.0), 2.0, 1.0);
+        let ((anchor_pos, anchor_vel), (pos, vel)) = bounce(anchor, body);
+
+        assert_eq!((anchor_pos, anchor_vel), (anchor.0, anchor.1));
+        assert!((vel - Vector::new(1.0, 0.0)).norm() < 1e-5);
+        // all of the overlap is taken by the free body
+        assert!((pos - Point::new(2.0, 0.0)).norm() < 1e-5);
+
+        let ((pos1, vel1), (pos2, vel2)) =
+            bounce(anchor, (body.0, body.1, std::f32::INFINITY, 1.0));
+        assert_eq!(
+            (pos1, vel1, pos2, vel2),
+            (anchor.0, anchor.1, body.0, body.1)
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use crate::ecs::components::{Position, Preview, Trail};
-use crate::ecs::resources::{EnableTrails, RelativeTrails};
+use crate::ecs::resources::{EnableTrails, RelativeTrails, SelectedEntity, TrailScope};
 use specs::prelude::*;
 
 pub struct TrailSys;
@@ -12,11 +12,22 @@ impl<'a> System<'a> for TrailSys {
         ReadStorage<'a, Preview>,
         Read<'a, EnableTrails>,
         Read<'a, RelativeTrails>,
+        Read<'a, TrailScope>,
+        Read<'a, SelectedEntity>,
     );
 
     fn run(
         &mut self,
-        (positions, mut trails, entities, previews, trails_enabled, relative_trails): Self::SystemData,
+        (
+            positions,
+            mut trails,
+            entities,
+            previews,
+            trails_enabled,
+            relative_trails,
+            trail_scope,
+            selected_entity,
+        ): Self::SystemData,
     ) {
         (&positions, &mut trails, &entities)
             .par_join()
@@ -29,11 +40,18 @@ impl<'a> System<'a> for TrailSys {
                     }
                 }
                 if previews.get(entity).is_none() {
-                    if trails_enabled.0 {
+                    if trails_enabled.0
+                        && trail.enabled
+                        && trail_scope.includes(entity, *selected_entity)
+                    {
                         trail.points.push_back(temp_pos);
                         while trail.points.len() >= trail.max_len.max(1) {
                             trail.points.pop_front();
                         }
+                        // a trail that was made shorter gives back the memory it no longer needs
+                        if trail.points.capacity() > 2 * trail.max_len.max(1) {
+                            trail.points.shrink_to_fit();
+                        }
                     } else {
                         trail.points.clear();
                     }
@@ -42,6 +60,44 @@ impl<'a> System<'a> for TrailSys {
     }
 }
 
+// Shortens the longest trails until there are at most max_points across all of them. Every trail
+// longer than some length is cut down to it, the length being the longest that fits. The
+// preview's trail is its prediction so it's left alone.
+pub fn cap_trail_points(
+    trails: &mut WriteStorage<'_, Trail>,
+    previews: &ReadStorage<'_, Preview>,
+    max_points: usize,
+) {
+    let lens: Vec<usize> = (&*trails, !previews)
+        .join()
+        .map(|(trail, _)| trail.points.len())
+        .collect();
+    let points_at = |len: usize| -> usize { lens.iter().map(|l| (*l).min(len)).sum() };
+    if points_at(usize::max_value()) <= max_points {
+        return;
+    }
+
+    let (mut low, mut high) = (0, lens.iter().cloned().max().unwrap_or(0));
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if points_at(mid) <= max_points {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    (trails, !previews).join().for_each(|(trail, _)| {
+        if trail.points.len() > low {
+            let excess = trail.points.len() - low;
+            trail.points.drain(..excess);
+            if trail.points.capacity() > 2 * low.max(1) {
+                trail.points.shrink_to_fit();
+            }
+        }
+    });
+}
+
 pub struct PreviewTrailSys;
 
 impl<'a> System<'a> for PreviewTrailSys {
@@ -59,3 +115,54 @@ impl<'a> System<'a> for PreviewTrailSys {
             });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::entities::{create_body, create_preview, new_body, new_preview};
+    use crate::ecs::world::new_world;
+
+    #[test]
+    fn trails_stay_within_max_len() {
+        let mut world = new_world();
+        let mut body = new_body([0.0, 0.0], [0.0, 0.0], 1.0, 1.0);
+        body.5 = Trail::new(10);
+        let e = create_body(&mut world, body);
+
+        for i in 0..1000 {
+            world.write_storage::<Position>().get_mut(e).unwrap().0.x = i as f32;
+            TrailSys.run_now(&world);
+            assert!(world.read_storage::<Trail>().get(e).unwrap().points.len() <= 10);
+        }
+    }
+
+    #[test]
+    fn capping_leaves_the_preview_trail() {
+        let mut world = new_world();
+        let body = create_body(&mut world, new_body([0.0, 0.0], [0.0, 0.0], 1.0, 1.0));
+        let preview = create_preview(&mut world, new_preview([5.0, 0.0], [0.0, 0.0], 1.0));
+
+        for i in 0..100 {
+            let mut trails = world.write_storage::<Trail>();
+            trails
+                .get_mut(body)
+                .unwrap()
+                .points
+                .push_back([i as f32, 0.0].into());
+            trails
+                .get_mut(preview)
+                .unwrap()
+                .points
+                .push_back([i as f32, 1.0].into());
+        }
+        cap_trail_points(
+            &mut world.write_storage::<Trail>(),
+            &world.read_storage::<Preview>(),
+            50,
+        );
+
+        let trails = world.read_storage::<Trail>();
+        assert_eq!(trails.get(body).unwrap().points.len(), 50);
+        assert_eq!(trails.get(preview).unwrap().points.len(), 100);
+    }
+}
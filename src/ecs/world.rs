@@ -0,0 +1,284 @@
+use specs::prelude::*;
+use specs::saveload::{SimpleMarker, SimpleMarkerAllocator};
+
+use crate::ecs::components::*;
+use crate::ecs::entities::{create_body, Body};
+use crate::ecs::resources::{
+    BarnesHutTheta, Boundary, CollisionIterations, CollisionLog, CollisionMode, Density,
+    DiagnosticsLog, EnableTrails, GravityConstant, GravityMode, InspiralDamping, Integrator,
+    IsolateSelected, MaxBodyMass, MaxSpeed, MaxTrailPoints, MergeThreshold, NanPolicy,
+    PhysicsTimings, ReferenceFrame, RelativeTrails, SelectedEntity, Softening, StickThreshold,
+    Substeps, SystemStats, TrailScope, WorldSize, DT,
+};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// A world with every component registered and the resources that physics::do_physics reads,
+// anything only the main world needs is inserted in main.
+pub fn new_world() -> World {
+    let mut world = World::new();
+
+    world.register::<Position>();
+    world.register::<Preview>();
+    world.register::<Kinematics>();
+    world.register::<Mass>();
+    world.register::<Draw>();
+    world.register::<Radius>();
+    world.register::<Trail>();
+    world.register::<Label>();
+    world.register::<Anchored>();
+    world.register::<Aggregate>();
+    world.register::<SpeedGraph>();
+    world.register::<XVelGraph>();
+    world.register::<YVelGraph>();
+    world.register::<AccelGraph>();
+    world.register::<AccelMagGraph>();
+    world.register::<SimpleMarker<SaveMarker>>();
+    world.insert(SimpleMarkerAllocator::<SaveMarker>::new());
+
+    world.insert(DT(1.0));
+    world.insert(GravityConstant::default());
+    world.insert(Softening::default());
+    world.insert(Integrator::VelocityVerlet);
+    world.insert(GravityMode::BruteForce);
+    world.insert(BarnesHutTheta::default());
+    world.insert(CollisionIterations(1));
+    world.insert(Substeps::default());
+    world.insert(CollisionMode::Merge);
+    world.insert(Boundary::None);
+    world.insert(WorldSize::default());
+    world.insert(MaxBodyMass(None));
+    world.insert(MergeThreshold::default());
+    world.insert(StickThreshold::default());
+    world.insert(MaxSpeed(None));
+    world.insert(Density(None));
+    world.insert(InspiralDamping(0.0));
+    world.insert(EnableTrails(true));
+    world.insert(MaxTrailPoints::default());
+    world.insert(PhysicsTimings::default());
+    world.insert(RelativeTrails::new());
+    world.insert(TrailScope::All);
+    world.insert(SelectedEntity(None));
+    world.insert(ReferenceFrame(None));
+    world.insert(IsolateSelected(false));
+    world.insert(CollisionLog::default());
+    world.insert(NanPolicy::default());
+    world.insert(DiagnosticsLog::default());
+    world.insert(SystemStats::default());
+
+    world
+}
+
+// Copies every body, its label, whether it's anchored and the aggregates they're in into a new
+// world, previews and graphs are left out. Returns the new world along with the copy of each
+// original entity.
+pub fn clone_bodies(world: &World) -> (World, HashMap<Entity, Entity>) {
+    let bodies: Vec<(Entity, (Position, Kinematics, Mass, Draw, Radius, Trail))> = {
+        let positions = world.read_storage::<Position>();
+        let kinematics = world.read_storage::<Kinematics>();
+        let masses = world.read_storage::<Mass>();
+        let draws = world.read_storage::<Draw>();
+        let radii = world.read_storage::<Radius>();
+        let trails = world.read_storage::<Trail>();
+        let entities = world.entities();
+
+        (
+            &positions,
+            &kinematics,
+            &masses,
+            &draws,
+            &radii,
+            &trails,
+            &entities,
+        )
+            .join()
+            .map(|(pos, kine, mass, draw, rad, trail, entity)| {
+                (entity, (*pos, *kine, *mass, *draw, *rad, trail.clone()))
+            })
+            .collect()
+    };
+
+    let mut new_world = new_world();
+    let copies: HashMap<Entity, Entity> = bodies
+        .into_iter()
+        .map(|(entity, body)| (entity, create_body(&mut new_world, body)))
+        .collect();
+
+    {
+        let aggregates = world.read_storage::<Aggregate>();
+        let mut new_aggregates = new_world.write_storage::<Aggregate>();
+        copies.iter().for_each(|(entity, copy)| {
+            if let Some(aggregate) = aggregates.get(*entity) {
+                new_aggregates
+                    .insert(*copy, *aggregate)
+                    .expect("error copying aggregate");
+            }
+        });
+
+        let labels = world.read_storage::<Label>();
+        let mut new_labels = new_world.write_storage::<Label>();
+        copies.iter().for_each(|(entity, copy)| {
+            if let Some(label) = labels.get(*entity) {
+                new_labels
+                    .insert(*copy, label.clone())
+                    .expect("error copying label");
+            }
+        });
+
+        let anchors = world.read_storage::<Anchored>();
+        let mut new_anchors = new_world.write_storage::<Anchored>();
+        copies.iter().for_each(|(entity, copy)| {
+            if anchors.contains(*entity) {
+                new_anchors
+                    .insert(*copy, Anchored)
+                    .expect("error copying anchor");
+            }
+        });
+    }
+
+    (new_world, copies)
+}
+
+// Every body at one moment, for rewinding. Trails aren't kept, only their settings so bodies that
+// are made again look the same.
+pub struct Snapshot {
+    bodies: Vec<(Entity, Body, Option<Label>, Option<Aggregate>, bool)>,
+}
+
+impl Snapshot {
+    pub fn capture(world: &World) -> Self {
+        let positions = world.read_storage::<Position>();
+        let kinematics = world.read_storage::<Kinematics>();
+        let masses = world.read_storage::<Mass>();
+        let draws = world.read_storage::<Draw>();
+        let radii = world.read_storage::<Radius>();
+        let trails = world.read_storage::<Trail>();
+        let labels = world.read_storage::<Label>();
+        let aggregates = world.read_storage::<Aggregate>();
+        let anchors = world.read_storage::<Anchored>();
+        let entities = world.entities();
+
+        let bodies = (
+            &entities,
+            &positions,
+            &kinematics,
+            &masses,
+            &draws,
+            &radii,
+            &trails,
+        )
+            .join()
+            .map(|(entity, pos, kine, mass, draw, rad, trail)| {
+                let trail = Trail {
+                    points: VecDeque::new(),
+                    max_len: trail.max_len,
+                    enabled: trail.enabled,
+                    color: trail.color,
+                };
+                (
+                    entity,
+                    (*pos, *kine, *mass, *draw, *rad, trail),
+                    labels.get(entity).cloned(),
+                    aggregates.get(entity).copied(),
+                    anchors.contains(entity),
+                )
+            })
+            .collect();
+
+        Snapshot { bodies }
+    }
+
+    // Puts every body back how it was. Bodies made since are deleted and bodies destroyed since
+    // are made again, returns the entities they had mapped to their new ones.
+    pub fn restore(&self, world: &mut World) -> HashMap<Entity, Entity> {
+        let later: Vec<Entity> = {
+            let masses = world.read_storage::<Mass>();
+            let kept: HashSet<Entity> = self.bodies.iter().map(|body| body.0).collect();
+            (&world.entities(), &masses)
+                .join()
+                .map(|(entity, _)| entity)
+                .filter(|entity| !kept.contains(entity))
+                .collect()
+        };
+        world
+            .delete_entities(&later)
+            .expect("error deleting bodies made after the snapshot");
+
+        let mut remade = HashMap::new();
+        self.bodies
+            .iter()
+            .for_each(|(entity, body, label, aggregate, anchored)| {
+                let entity =
+                    if world.is_alive(*entity) && world.read_storage::<Mass>().contains(*entity) {
+                        world
+                            .write_storage::<Position>()
+                            .insert(*entity, body.0)
+                            .unwrap_or(None);
+                        world
+                            .write_storage::<Kinematics>()
+                            .insert(*entity, body.1)
+                            .unwrap_or(None);
+                        world
+                            .write_storage::<Mass>()
+                            .insert(*entity, body.2)
+                            .unwrap_or(None);
+                        world
+                            .write_storage::<Draw>()
+                            .insert(*entity, body.3)
+                            .unwrap_or(None);
+                        world
+                            .write_storage::<Radius>()
+                            .insert(*entity, body.4)
+                            .unwrap_or(None);
+                        *entity
+                    } else {
+                        let new_entity = create_body(world, body.clone());
+                        remade.insert(*entity, new_entity);
+                        new_entity
+                    };
+
+                let mut labels = world.write_storage::<Label>();
+                match label {
+                    Some(label) => {
+                        labels
+                            .insert(entity, label.clone())
+                            .expect("error restoring label");
+                    }
+                    None => {
+                        labels.remove(entity);
+                    }
+                }
+                let mut aggregates = world.write_storage::<Aggregate>();
+                match aggregate {
+                    Some(aggregate) => {
+                        aggregates
+                            .insert(entity, *aggregate)
+                            .expect("error restoring aggregate");
+                    }
+                    None => {
+                        aggregates.remove(entity);
+                    }
+                }
+                let mut anchors = world.write_storage::<Anchored>();
+                if *anchored {
+                    anchors
+                        .insert(entity, Anchored)
+                        .expect("error restoring anchor");
+                } else {
+                    anchors.remove(entity);
+                }
+            });
+        world.maintain();
+
+        remade
+    }
+
+    // points bodies that restore made again at their new entities
+    pub fn remap(&mut self, remade: &HashMap<Entity, Entity>) {
+        self.bodies.iter_mut().for_each(|body| {
+            if let Some(new_entity) = remade.get(&body.0) {
+                body.0 = *new_entity;
+            }
+        });
+    }
+}
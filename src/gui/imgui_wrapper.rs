@@ -11,8 +11,14 @@ use imgui::StyleColor;
 use imgui::*;
 use imgui_gfx_renderer::*;
 
+use crate::ecs::components::{BodyId, TRAIL_COLOR};
+use crate::ecs::resources::{
+    AimMode, Boundary, CollisionMode, GravityMode, Integrator, NanPolicy, PhysicsTimings,
+    SystemStats, TimeMode,
+};
 use crate::ecs::systems::graph_sys::GraphType;
 use crate::gui::ui::*;
+use crate::presets::Preset;
 use crate::Vector;
 
 use specs::prelude::*;
@@ -33,11 +39,23 @@ pub enum UiChoice {
     DefaultUI,
     SideMenu(Option<Entity>),
     Graph,
+    Stats,
+    Timings,
+}
+
+// what left clicking and dragging does
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Tool {
+    Select,
+    Create,
+    Measure,
+    Move,
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum UiSignal {
-    Create,
+    SetTool(Tool),
+    SpawnPreset(Preset),
     Delete,
     AddGraph(GraphType),
     ToggleGraphs,
@@ -45,8 +63,26 @@ pub enum UiSignal {
     LoadState,
     DeleteAll,
     ToggleFollowBody,
+    TogglePrediction,
+    ToggleRecording,
+    ExportTrajectory,
+    ClearTrail,
     ToggleTrails,
     ToggleRelativeTrails,
+    ToggleTrailScope,
+    ToggleTrailTaper,
+    ToggleTrailFade,
+    ToggleMetaballs,
+    ToggleCenterOfMass,
+    ToggleLogRadius,
+    ToggleSpeedColors,
+    ToggleIsolateSelected,
+    ToggleStats,
+    ToggleTimings,
+    Perturb,
+    ClearPerturbation,
+    ApplyKick,
+    ApplySpin,
     Pause,
 }
 
@@ -54,15 +90,85 @@ pub enum UiSignal {
 pub struct RenderData {
     pub create_mass: f32,
     pub create_rad: f32,
+    pub create_color: [f32; 4],
+    pub create_circular_orbit: bool,
+    pub launch_scale: f32,
+    pub nudge_step: f32,
+    pub aim_mode: AimMode,
     pub mass: f32,
     pub rad: f32,
+    pub color: [f32; 4],
+    // read only, shown in the side menu
+    pub pos: [f32; 2],
+    pub vel: [f32; 2],
+    pub accel_mag: f32,
+    pub bound: bool,
+    pub escape_speed: f32,
     pub dt: f32,
+    pub time_mode: TimeMode,
+    pub g: f32,
+    pub softening: f32,
     pub num_iterations: usize,
     pub preview_iterations: usize,
+    pub collision_iterations: usize,
+    pub substeps: usize,
     pub entity_selected: bool,
+    pub body_id: Option<BodyId>,
+    pub label: ImString,
+    pub anchored: bool,
     pub save_filename: ImString,
     pub load_filename: ImString,
     pub trail_len: usize,
+    pub trail_enabled: bool,
+    pub trail_color: [f32; 4],
+    pub autosave_enabled: bool,
+    pub autosave_steps: usize,
+    pub autosave_mins: f32,
+    pub autosave_keep: usize,
+    pub status: String,
+    pub recording: bool,
+    pub stats: SystemStats,
+    pub timings: PhysicsTimings,
+    pub collision_log: Vec<String>,
+    pub diagnostics_log: Vec<String>,
+    pub perturb_epsilon: f32,
+    pub kick: [f32; 2],
+    pub spin: f32,
+    pub tool: Tool,
+    pub density_enabled: bool,
+    pub density: f32,
+    pub max_speed_enabled: bool,
+    pub max_speed: f32,
+    pub max_mass_enabled: bool,
+    pub max_mass: f32,
+    pub stats_log_enabled: bool,
+    pub stats_log_steps: usize,
+    pub stats_log_max_rows: usize,
+    pub rewind_depth: usize,
+    pub pause_on_focus_loss: bool,
+    pub zoom_smoothing: bool,
+    pub integrator: Integrator,
+    pub gravity_mode: GravityMode,
+    pub barnes_hut_theta: f32,
+    pub collision_mode: CollisionMode,
+    pub merge_threshold: f32,
+    pub stick_threshold_enabled: bool,
+    pub stick_threshold: f32,
+    pub boundary: Boundary,
+    pub nan_policy: NanPolicy,
+    pub world_size: [f32; 2],
+    pub inspiral_damping: f32,
+    pub lod_enabled: bool,
+    pub lod_cell_pixels: f32,
+    pub grid_enabled: bool,
+    pub grid_spacing: f32,
+    pub motion_arrows_enabled: bool,
+    pub motion_arrow_scale: f32,
+    pub potential_map_enabled: bool,
+    pub potential_map_resolution: usize,
+    pub cloud_count: usize,
+    pub cloud_seed: i32,
+    pub cloud_spin: f32,
 }
 
 impl RenderData {
@@ -70,15 +176,84 @@ impl RenderData {
         RenderData {
             create_mass: 0.01,
             create_rad: 1.0,
+            create_color: [1.0, 1.0, 1.0, 1.0],
+            create_circular_orbit: false,
+            launch_scale: 0.025,
+            nudge_step: 0.01,
+            aim_mode: AimMode::Slingshot,
             mass: 0.01,
             rad: 1.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            pos: [0.0, 0.0],
+            vel: [0.0, 0.0],
+            accel_mag: 0.0,
+            bound: false,
+            escape_speed: 0.0,
             dt: 1.0,
+            time_mode: TimeMode::FixedStep,
+            g: crate::G,
+            softening: 1.0,
             num_iterations: 1,
             preview_iterations: 25,
+            collision_iterations: 1,
+            substeps: 1,
             entity_selected: false,
+            body_id: None,
+            label: ImString::new(""),
+            anchored: false,
             save_filename: ImString::new("save.ron"),
             load_filename: ImString::new("load.ron"),
             trail_len: 35,
+            trail_enabled: true,
+            trail_color: TRAIL_COLOR.into(),
+            autosave_enabled: false,
+            autosave_steps: 10_000,
+            autosave_mins: 5.0,
+            autosave_keep: 3,
+            status: String::new(),
+            recording: false,
+            stats: SystemStats::default(),
+            timings: PhysicsTimings::default(),
+            collision_log: Vec::new(),
+            diagnostics_log: Vec::new(),
+            perturb_epsilon: 0.001,
+            kick: [0.1, 0.0],
+            spin: 0.001,
+            tool: Tool::Select,
+            density_enabled: false,
+            density: 1.0,
+            max_speed_enabled: false,
+            max_speed: 10.0,
+            max_mass_enabled: false,
+            max_mass: 100.0,
+            stats_log_enabled: false,
+            stats_log_steps: 100,
+            stats_log_max_rows: 100_000,
+            rewind_depth: 0,
+            pause_on_focus_loss: false,
+            zoom_smoothing: true,
+            integrator: Integrator::VelocityVerlet,
+            gravity_mode: GravityMode::BruteForce,
+            barnes_hut_theta: 0.5,
+            collision_mode: CollisionMode::Merge,
+            merge_threshold: 1.0,
+            stick_threshold_enabled: false,
+            stick_threshold: 1.0,
+            boundary: Boundary::None,
+            nan_policy: NanPolicy::Delete,
+            world_size: [crate::SCREEN_X, crate::SCREEN_Y],
+            inspiral_damping: 0.0,
+            lod_enabled: false,
+            lod_cell_pixels: 4.0,
+            grid_enabled: false,
+            grid_spacing: 25.0,
+            motion_arrows_enabled: false,
+            motion_arrow_scale: 10.0,
+            potential_map_enabled: false,
+            potential_map_resolution: 32,
+            cloud_count: 100,
+            cloud_seed: 0,
+            cloud_spin: 0.0,
         }
     }
 }
@@ -93,6 +268,8 @@ pub struct ImGuiWrapper {
     pub resolution: Vector,
     pub sidemenu: bool,
     pub graph: bool,
+    pub stats: bool,
+    pub timings: bool,
     pub render_data: RenderData,
 }
 
@@ -169,6 +346,8 @@ impl ImGuiWrapper {
             resolution,
             sidemenu: false,
             graph: false,
+            stats: false,
+            timings: false,
             render_data: RenderData::new(),
         }
     }
@@ -223,6 +402,19 @@ impl ImGuiWrapper {
                             );
                         }
                     }
+                    UiChoice::Stats => {
+                        self.stats = true;
+                        make_stats_ui(&mut ui, self.resolution, &mut self.stats, &self.render_data);
+                    }
+                    UiChoice::Timings => {
+                        self.timings = true;
+                        make_timings_ui(
+                            &mut ui,
+                            self.resolution,
+                            &mut self.timings,
+                            &self.render_data,
+                        );
+                    }
                     _ => unimplemented!(),
                 }
             }
@@ -248,6 +440,12 @@ impl ImGuiWrapper {
         if !self.graph {
             self.shown_menus.remove(&UiChoice::Graph);
         }
+        if !self.stats {
+            self.shown_menus.remove(&UiChoice::Stats);
+        }
+        if !self.timings {
+            self.shown_menus.remove(&UiChoice::Timings);
+        }
     }
 
     pub fn remove_sidemenu(&mut self) {
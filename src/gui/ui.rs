@@ -2,8 +2,12 @@ use imgui::*;
 use std::fs;
 use std::path::Path;
 
+use crate::ecs::resources::{
+    AimMode, Boundary, CollisionMode, GravityMode, Integrator, NanPolicy, TimeMode,
+};
 use crate::ecs::systems::graph_sys::GraphType;
 use crate::gui::imgui_wrapper::*;
+use crate::presets::Preset;
 use crate::Vector;
 
 macro_rules! signal_button {
@@ -42,11 +46,49 @@ pub fn make_menu_bar(
             ui.drag_float(im_str!("Mass"), create_mass)
                 .speed(mass_speed)
                 .build();
-            ui.drag_float(im_str!("Radius"), create_rad)
-                .speed(rad_speed)
+            // the radius follows the mass when it's linked
+            if !render_data.density_enabled {
+                ui.drag_float(im_str!("Radius"), create_rad)
+                    .speed(rad_speed)
+                    .build();
+            }
+            imgui::ColorEdit::new(im_str!("Color"), &mut render_data.create_color)
+                .alpha(true)
+                .build(ui);
+            ui.drag_float(im_str!("Launch Scale"), &mut render_data.launch_scale)
+                .min(0.0)
+                .speed(0.0005)
+                .build();
+            ui.drag_float(im_str!("Nudge Step"), &mut render_data.nudge_step)
+                .min(0.0)
+                .speed(0.0005)
                 .build();
+            ui.radio_button(
+                im_str!("Slingshot Aim"),
+                &mut render_data.aim_mode,
+                AimMode::Slingshot,
+            );
+            ui.radio_button(
+                im_str!("Direct Aim"),
+                &mut render_data.aim_mode,
+                AimMode::Direct,
+            );
+            ui.checkbox(
+                im_str!("Circular Orbit"),
+                &mut render_data.create_circular_orbit,
+            );
 
-            signal_button!("Toggle Create Body", UiSignal::Create, ui, signals);
+            signal_button!("Create Tool", UiSignal::SetTool(Tool::Create), ui, signals);
+        });
+
+        ui.separator();
+
+        ui.menu(im_str!("Tools"), true, || {
+            ui.text(format!("Current: {:?}", render_data.tool));
+            signal_button!("Select", UiSignal::SetTool(Tool::Select), ui, signals);
+            signal_button!("Create", UiSignal::SetTool(Tool::Create), ui, signals);
+            signal_button!("Measure", UiSignal::SetTool(Tool::Measure), ui, signals);
+            signal_button!("Move", UiSignal::SetTool(Tool::Move), ui, signals);
         });
 
         ui.separator();
@@ -55,10 +97,178 @@ pub fn make_menu_bar(
             let dt = &mut render_data.dt;
             let num_iterations = &mut render_data.num_iterations;
             let preview_iterations = &mut render_data.preview_iterations;
+            let collision_iterations = &mut render_data.collision_iterations;
+            let substeps = &mut render_data.substeps;
 
+            ui.text(format!("Time Scale: {}x", dt));
             ui.drag_float(im_str!("Timestep"), dt).speed(0.01).build();
+            ui.radio_button(
+                im_str!("Fixed Step"),
+                &mut render_data.time_mode,
+                TimeMode::FixedStep,
+            );
+            ui.radio_button(
+                im_str!("Real Time"),
+                &mut render_data.time_mode,
+                TimeMode::RealTime,
+            );
+            ui.checkbox(
+                im_str!("Pause on Focus Loss"),
+                &mut render_data.pause_on_focus_loss,
+            );
+            ui.drag_float(im_str!("Gravitational Constant"), &mut render_data.g)
+                .speed(0.01)
+                .build();
+            ui.drag_float(im_str!("Softening"), &mut render_data.softening)
+                .min(0.0)
+                .speed(0.01)
+                .build();
+            ui.slider_float(
+                im_str!("Inspiral Damping"),
+                &mut render_data.inspiral_damping,
+                0.0,
+                1000.0,
+            )
+            .build();
             int_slider!(ui, "Iterations", num_iterations, 1, 1000);
+            int_slider!(ui, "Substeps", substeps, 1, 100);
             int_slider!(ui, "Preview Iterations", preview_iterations, 1, 1000);
+            int_slider!(ui, "Collision Iterations", collision_iterations, 1, 100);
+            ui.radio_button(
+                im_str!("Euler"),
+                &mut render_data.integrator,
+                Integrator::Euler,
+            );
+            ui.radio_button(
+                im_str!("Leapfrog"),
+                &mut render_data.integrator,
+                Integrator::Leapfrog,
+            );
+            ui.radio_button(
+                im_str!("Velocity Verlet"),
+                &mut render_data.integrator,
+                Integrator::VelocityVerlet,
+            );
+            ui.radio_button(im_str!("RK4"), &mut render_data.integrator, Integrator::RK4);
+            ui.radio_button(
+                im_str!("Exact Gravity"),
+                &mut render_data.gravity_mode,
+                GravityMode::BruteForce,
+            );
+            ui.radio_button(
+                im_str!("Barnes-Hut Gravity"),
+                &mut render_data.gravity_mode,
+                GravityMode::BarnesHut,
+            );
+            if render_data.gravity_mode == GravityMode::BarnesHut {
+                ui.slider_float(
+                    im_str!("Barnes-Hut Theta"),
+                    &mut render_data.barnes_hut_theta,
+                    0.0,
+                    1.5,
+                )
+                .build();
+            }
+            ui.radio_button(
+                im_str!("Merge Collisions"),
+                &mut render_data.collision_mode,
+                CollisionMode::Merge,
+            );
+            ui.radio_button(
+                im_str!("Aggregate Collisions"),
+                &mut render_data.collision_mode,
+                CollisionMode::Aggregate,
+            );
+            ui.radio_button(
+                im_str!("Bounce Collisions"),
+                &mut render_data.collision_mode,
+                CollisionMode::Bounce,
+            );
+            if render_data.collision_mode == CollisionMode::Merge {
+                ui.slider_float(
+                    im_str!("Merge Threshold"),
+                    &mut render_data.merge_threshold,
+                    0.0,
+                    2.0,
+                )
+                .build();
+                ui.checkbox(
+                    im_str!("Bounce Fast Collisions"),
+                    &mut render_data.stick_threshold_enabled,
+                );
+                if render_data.stick_threshold_enabled {
+                    ui.slider_float(
+                        im_str!("Stick Threshold"),
+                        &mut render_data.stick_threshold,
+                        0.0,
+                        10.0,
+                    )
+                    .build();
+                }
+            }
+            ui.radio_button(
+                im_str!("No Boundary"),
+                &mut render_data.boundary,
+                Boundary::None,
+            );
+            ui.radio_button(
+                im_str!("Wrap Boundary"),
+                &mut render_data.boundary,
+                Boundary::Wrap,
+            );
+            ui.radio_button(
+                im_str!("Reflect Boundary"),
+                &mut render_data.boundary,
+                Boundary::Reflect,
+            );
+            if render_data.boundary != Boundary::None {
+                ui.drag_float2(im_str!("World Size"), &mut render_data.world_size)
+                    .min(1.0)
+                    .build();
+            }
+            ui.radio_button(
+                im_str!("Delete Non-finite Bodies"),
+                &mut render_data.nan_policy,
+                NanPolicy::Delete,
+            );
+            ui.radio_button(
+                im_str!("Clamp Non-finite Bodies"),
+                &mut render_data.nan_policy,
+                NanPolicy::Clamp,
+            );
+            ui.radio_button(
+                im_str!("Ignore Non-finite Bodies"),
+                &mut render_data.nan_policy,
+                NanPolicy::Ignore,
+            );
+            ui.checkbox(
+                im_str!("Link Radius to Mass"),
+                &mut render_data.density_enabled,
+            );
+            if render_data.density_enabled {
+                ui.drag_float(im_str!("Density"), &mut render_data.density)
+                    .min(0.001)
+                    .speed(0.01)
+                    .build();
+            }
+            ui.checkbox(im_str!("Limit Speed"), &mut render_data.max_speed_enabled);
+            if render_data.max_speed_enabled {
+                ui.drag_float(im_str!("Max Speed"), &mut render_data.max_speed)
+                    .min(0.01)
+                    .speed(0.01)
+                    .build();
+            }
+            ui.checkbox(
+                im_str!("Limit Body Mass"),
+                &mut render_data.max_mass_enabled,
+            );
+            if render_data.max_mass_enabled {
+                let max_mass_speed = (render_data.max_mass * 0.0015).max(0.01);
+                ui.drag_float(im_str!("Max Body Mass"), &mut render_data.max_mass)
+                    .min(0.01)
+                    .speed(max_mass_speed)
+                    .build();
+            }
 
             signal_button!("Toggle Graphs", UiSignal::ToggleGraphs, ui, signals);
             signal_button!("Toggle Trails", UiSignal::ToggleTrails, ui, signals);
@@ -68,6 +278,145 @@ pub fn make_menu_bar(
                 ui,
                 signals
             );
+            signal_button!(
+                "Toggle Selected Trails Only",
+                UiSignal::ToggleTrailScope,
+                ui,
+                signals
+            );
+            signal_button!(
+                "Toggle Trail Taper",
+                UiSignal::ToggleTrailTaper,
+                ui,
+                signals
+            );
+            signal_button!("Toggle Trail Fade", UiSignal::ToggleTrailFade, ui, signals);
+            signal_button!("Toggle Metaballs", UiSignal::ToggleMetaballs, ui, signals);
+            signal_button!(
+                "Toggle Center of Mass",
+                UiSignal::ToggleCenterOfMass,
+                ui,
+                signals
+            );
+            ui.checkbox(im_str!("Smooth Zoom"), &mut render_data.zoom_smoothing);
+            ui.checkbox(im_str!("Grid"), &mut render_data.grid_enabled);
+            if render_data.grid_enabled {
+                ui.drag_float(im_str!("Grid Spacing"), &mut render_data.grid_spacing)
+                    .min(0.1)
+                    .speed(0.1)
+                    .build();
+            }
+            ui.checkbox(
+                im_str!("Velocity Arrows"),
+                &mut render_data.motion_arrows_enabled,
+            );
+            if render_data.motion_arrows_enabled {
+                ui.drag_float(im_str!("Arrow Scale"), &mut render_data.motion_arrow_scale)
+                    .min(0.1)
+                    .speed(0.1)
+                    .build();
+            }
+            ui.checkbox(
+                im_str!("Potential Map"),
+                &mut render_data.potential_map_enabled,
+            );
+            if render_data.potential_map_enabled {
+                int_slider!(
+                    ui,
+                    "Potential Map Resolution",
+                    &mut render_data.potential_map_resolution,
+                    4,
+                    128
+                );
+            }
+            ui.checkbox(
+                im_str!("Group Distant Bodies"),
+                &mut render_data.lod_enabled,
+            );
+            if render_data.lod_enabled {
+                ui.drag_float(
+                    im_str!("Group Cell Pixels"),
+                    &mut render_data.lod_cell_pixels,
+                )
+                .min(1.0)
+                .speed(0.1)
+                .build();
+            }
+            signal_button!("Toggle Log Radius", UiSignal::ToggleLogRadius, ui, signals);
+            signal_button!(
+                "Toggle Speed Colors",
+                UiSignal::ToggleSpeedColors,
+                ui,
+                signals
+            );
+            signal_button!("Toggle Stats", UiSignal::ToggleStats, ui, signals);
+            signal_button!("Toggle Timings", UiSignal::ToggleTimings, ui, signals);
+            signal_button!(
+                "Toggle Isolate Selected",
+                UiSignal::ToggleIsolateSelected,
+                ui,
+                signals
+            );
+        });
+
+        ui.separator();
+
+        ui.menu(im_str!("Presets"), true, || {
+            ui.text(im_str!("Replaces everything"));
+            signal_button!(
+                "Binary Star",
+                UiSignal::SpawnPreset(Preset::Binary),
+                ui,
+                signals
+            );
+            signal_button!(
+                "Solar System",
+                UiSignal::SpawnPreset(Preset::SolarSystem),
+                ui,
+                signals
+            );
+
+            ui.separator();
+            int_slider!(ui, "Count", &mut render_data.cloud_count, 1, 5000);
+            ui.input_int(im_str!("Seed"), &mut render_data.cloud_seed)
+                .build();
+            ui.drag_float(im_str!("Spin"), &mut render_data.cloud_spin)
+                .speed(0.0001)
+                .build();
+            let cloud = Preset::RandomCloud {
+                count: render_data.cloud_count,
+                seed: render_data.cloud_seed as u64,
+                spin: render_data.cloud_spin,
+            };
+            signal_button!("Random Cloud", UiSignal::SpawnPreset(cloud), ui, signals);
+        });
+
+        ui.separator();
+
+        ui.menu(im_str!("Perturb"), true, || {
+            ui.drag_float(im_str!("Epsilon"), &mut render_data.perturb_epsilon)
+                .speed(0.0001)
+                .display_format(im_str!("%.5f"))
+                .build();
+            signal_button!("Perturb", UiSignal::Perturb, ui, signals);
+            signal_button!(
+                "Clear Perturbation",
+                UiSignal::ClearPerturbation,
+                ui,
+                signals
+            );
+        });
+
+        ui.menu(im_str!("System Kick"), true, || {
+            ui.drag_float2(im_str!("Kick"), &mut render_data.kick)
+                .speed(0.01)
+                .build();
+            signal_button!("Apply Kick", UiSignal::ApplyKick, ui, signals);
+            ui.drag_float(im_str!("Spin"), &mut render_data.spin)
+                .speed(0.0001)
+                .display_format(im_str!("%.5f"))
+                .build();
+            signal_button!("Apply Spin", UiSignal::ApplySpin, ui, signals);
         });
 
         ui.separator();
@@ -106,6 +455,43 @@ pub fn make_menu_bar(
 
         ui.separator();
 
+        ui.menu(im_str!("Auto Save"), true, || {
+            ui.checkbox(im_str!("Enabled"), &mut render_data.autosave_enabled);
+
+            let autosave_steps = &mut render_data.autosave_steps;
+            let autosave_keep = &mut render_data.autosave_keep;
+            int_slider!(ui, "Every N Steps", autosave_steps, 0, 1_000_000);
+            ui.drag_float(im_str!("Every N Minutes"), &mut render_data.autosave_mins)
+                .min(0.0)
+                .speed(0.05)
+                .build();
+            int_slider!(ui, "Checkpoints Kept", autosave_keep, 1, 100);
+        });
+
+        ui.separator();
+
+        ui.menu(im_str!("Stats Log"), true, || {
+            ui.checkbox(
+                im_str!("Log to stats.csv"),
+                &mut render_data.stats_log_enabled,
+            );
+
+            let stats_log_steps = &mut render_data.stats_log_steps;
+            let stats_log_max_rows = &mut render_data.stats_log_max_rows;
+            int_slider!(ui, "Every N Steps", stats_log_steps, 1, 1_000_000);
+            int_slider!(ui, "Max Rows", stats_log_max_rows, 1, 10_000_000);
+        });
+
+        ui.separator();
+
+        ui.menu(im_str!("Rewind"), true, || {
+            let rewind_depth = &mut render_data.rewind_depth;
+            int_slider!(ui, "Steps Kept", rewind_depth, 0, 100_000);
+            ui.text("Each step kept takes about 190 bytes per body");
+        });
+
+        ui.separator();
+
         signal_button!("Reset", UiSignal::DeleteAll, ui, signals);
         ui.separator();
         signal_button!("Pause", UiSignal::Pause, ui, signals);
@@ -113,21 +499,60 @@ pub fn make_menu_bar(
         ui.separator();
         ui.separator();
 
+        if !render_data.status.is_empty() {
+            ui.text(&render_data.status);
+            ui.separator();
+        }
+
         if ui.small_button(im_str!("Help")) {
             ui.open_popup(im_str!("Help Menu"));
         }
         ui.popup_modal(im_str!("Help Menu")).build(|| {
-            ui.bullet_text(im_str!("WASD and arrows to move the camera"));
-            ui.bullet_text(im_str!("Space to pause"));
-            ui.bullet_text(im_str!("F to follow selected body, D to delete it"));
+            ui.bullet_text(im_str!(
+                "WASD and arrows to move the camera, Home to reset it, Z to fit every body"
+            ));
+            ui.bullet_text(im_str!(
+                "Space to pause, period to step forward while paused, hold comma to rewind"
+            ));
+            ui.bullet_text(im_str!(
+                "F to toggle following the selected body, R or Delete to delete it"
+            ));
+            ui.bullet_text(im_str!("O to pan to the origin, C to the center of mass"));
+            ui.bullet_text(im_str!("Ctrl + Z to undo creating a body"));
+            ui.bullet_text(im_str!("Ctrl + D to duplicate the selected body"));
+            ui.bullet_text(im_str!(
+                "Ctrl + arrow keys to nudge the selected body's velocity"
+            ));
+            ui.bullet_text(im_str!("F11 to toggle fullscreen, P to show the FPS"));
+            ui.bullet_text(im_str!("F12 to take a screenshot without the menus"));
+            ui.bullet_text(im_str!(
+                "F3 to show how long each part of the physics takes"
+            ));
             ui.bullet_text(im_str!("T to toggle trails, G to make them relative"));
             ui.bullet_text(im_str!("Right click a body to edit it"));
+            ui.bullet_text(im_str!(
+                "[ and ] to halve or double the timestep, \\ to reset it"
+            ));
+            ui.bullet_text(im_str!("Tab and Shift + Tab to step through the bodies"));
+            ui.bullet_text(im_str!(
+                "Shift + Right click a body to graph velocities relative to it"
+            ));
+            ui.bullet_text(im_str!(
+                "Ctrl + Right drag to measure between points or bodies"
+            ));
+            ui.bullet_text(im_str!(
+                "Pick what left click does from the Tools menu: select, create, measure or move"
+            ));
             ui.bullet_text(im_str!(
                 "Create new bodies and edit universal variables with the top bar"
             ));
             ui.bullet_text(im_str!(
                 "Save and load scenarios from the saved_systems folder"
             ));
+            ui.bullet_text(im_str!("Ctrl+S to save, Ctrl+O to reload that save"));
+            ui.bullet_text(im_str!(
+                "Record a body's path from its menu, Ctrl+E writes the recording to a csv"
+            ));
             if ui.small_button(im_str!("Close Help Menu")) {
                 ui.close_current_popup();
             }
@@ -143,7 +568,20 @@ pub fn make_sidepanel(
 ) {
     let mass = &mut render_data.mass;
     let rad = &mut render_data.rad;
+    let color = &mut render_data.color;
     let trail_len = &mut render_data.trail_len;
+    let trail_enabled = &mut render_data.trail_enabled;
+    let trail_color = &mut render_data.trail_color;
+    let label = &mut render_data.label;
+    let anchored = &mut render_data.anchored;
+    let body_id = render_data.body_id;
+    let density_enabled = render_data.density_enabled;
+    let pos = render_data.pos;
+    let vel = render_data.vel;
+    let accel_mag = render_data.accel_mag;
+    let bound = render_data.bound;
+    let escape_speed = render_data.escape_speed;
+    let recording = render_data.recording;
     assert!(render_data.entity_selected);
     // Window
     let win = imgui::Window::new(im_str!("Menu"))
@@ -163,19 +601,62 @@ pub fn make_sidepanel(
         //constructs a small button that sends a UiSignal
 
         ui.text(im_str!("Edit Object"));
+        if let Some(id) = body_id {
+            ui.text(format!("Body {}", id));
+        }
+        ui.input_text(im_str!("Name"), label)
+            .resize_buffer(true)
+            .build();
         let mass_speed = (*mass * 0.0015).max(0.01);
         let rad_speed = (*rad * 0.0015).max(0.01);
         ui.drag_float(im_str!("Mass"), mass)
             .speed(mass_speed)
             .build();
-        ui.drag_float(im_str!("Radius"), rad)
-            .speed(rad_speed)
-            .build();
+        if density_enabled {
+            ui.text(format!("Radius: {:.3}", rad));
+        } else {
+            ui.drag_float(im_str!("Radius"), rad)
+                .speed(rad_speed)
+                .build();
+        }
+        imgui::ColorEdit::new(im_str!("Color"), color)
+            .alpha(true)
+            .build(ui);
+
+        let speed = (vel[0].powi(2) + vel[1].powi(2)).sqrt();
+        ui.text(format!("Position: ({:.3}, {:.3})", pos[0], pos[1]));
+        ui.text(format!(
+            "Velocity: {:.4} ({:.4}, {:.4})",
+            speed, vel[0], vel[1]
+        ));
+        ui.text(format!("Acceleration: {:.5}", accel_mag));
+        ui.text(format!(
+            "Kinetic Energy: {:.4}",
+            0.5 * *mass * speed.powi(2)
+        ));
+        ui.text(format!(
+            "{} (escape velocity {:.4})",
+            if bound { "Bound" } else { "Escaping" },
+            escape_speed
+        ));
 
+        ui.checkbox(im_str!("Anchored"), anchored);
+        ui.checkbox(im_str!("Show Trail"), trail_enabled);
         int_slider!(ui, "Trail Length", trail_len, 0, 10_000);
+        imgui::ColorEdit::new(im_str!("Trail Color"), trail_color)
+            .alpha(true)
+            .build(ui);
+        signal_button!("Clear Trail", UiSignal::ClearTrail, ui, signals);
 
         signal_button!("Follow Body", UiSignal::ToggleFollowBody, ui, signals);
+        signal_button!("Predict Orbit", UiSignal::TogglePrediction, ui, signals);
         signal_button!("Delete Body", UiSignal::Delete, ui, signals);
+        if recording {
+            signal_button!("Stop Recording", UiSignal::ToggleRecording, ui, signals);
+            signal_button!("Export Trajectory", UiSignal::ExportTrajectory, ui, signals);
+        } else {
+            signal_button!("Record Trajectory", UiSignal::ToggleRecording, ui, signals);
+        }
 
         ui.spacing();
         ui.separator();
@@ -206,6 +687,12 @@ pub fn make_sidepanel(
                 ui,
                 signals
             );
+            signal_button!(
+                "Graph Acceleration Magnitude",
+                UiSignal::AddGraph(GraphType::AccelMag),
+                ui,
+                signals
+            );
         }
     });
 }
@@ -223,6 +710,7 @@ pub fn make_graph_ui(
         GraphType::Speed => im_str!("Speed"),
         GraphType::XVel => im_str!("X Velocity"),
         GraphType::YVel => im_str!("Y Velocity"),
+        GraphType::AccelMag => im_str!("Acceleration"),
         _ => panic!("{:#?} is not an imgui graph", graph_type),
     };
 
@@ -240,6 +728,96 @@ pub fn make_graph_ui(
         });
 }
 
+pub fn make_stats_ui(
+    ui: &mut imgui::Ui,
+    resolution: Vector,
+    open_bool: &mut bool,
+    render_data: &RenderData,
+) {
+    let stats = &render_data.stats;
+
+    imgui::Window::new(im_str!("Stats"))
+        .position(
+            [resolution.x * 0.6, resolution.y * 0.45],
+            imgui::Condition::Once,
+        )
+        .size(
+            [resolution.x * 0.4, resolution.y * 0.45],
+            imgui::Condition::Appearing,
+        )
+        .opened(open_bool)
+        .build(ui, || {
+            ui.text(format!("Kinetic Energy: {:.4}", stats.kinetic));
+            ui.text(format!("Potential Energy: {:.4}", stats.potential));
+            ui.text(format!("Total Energy: {:.4}", stats.total()));
+            ui.text(format!("Dissipated: {:.4}", stats.dissipated));
+            // this only changes due to integration error
+            ui.text(format!(
+                "Total + Lost: {:.4}",
+                stats.total() + stats.dissipated
+            ));
+            ui.text(format!(
+                "Momentum: ({:.4}, {:.4})",
+                stats.momentum.x, stats.momentum.y
+            ));
+            ui.text(format!("Angular Momentum: {:.4}", stats.angular_momentum));
+            ui.text(format!(
+                "Center of Mass: ({:.2}, {:.2})",
+                stats.center_of_mass.x, stats.center_of_mass.y
+            ));
+
+            ui.separator();
+            ui.text(im_str!("Recent Collisions"));
+            render_data
+                .collision_log
+                .iter()
+                .for_each(|line| ui.text(line));
+
+            if !render_data.diagnostics_log.is_empty() {
+                ui.separator();
+                ui.text(im_str!("Non-finite Bodies"));
+                render_data
+                    .diagnostics_log
+                    .iter()
+                    .for_each(|line| ui.text(line));
+            }
+        });
+}
+
+// Where the last frame's physics time went, each phase in microseconds and as a share of the total
+pub fn make_timings_ui(
+    ui: &mut imgui::Ui,
+    resolution: Vector,
+    open_bool: &mut bool,
+    render_data: &RenderData,
+) {
+    let timings = &render_data.timings;
+    let total = timings.total().as_secs_f64();
+
+    imgui::Window::new(im_str!("Physics Timings"))
+        .position(
+            [resolution.x * 0.6, resolution.y * 0.05],
+            imgui::Condition::Once,
+        )
+        .size(
+            [resolution.x * 0.4, resolution.y * 0.3],
+            imgui::Condition::Appearing,
+        )
+        .opened(open_bool)
+        .build(ui, || {
+            timings.phases().iter().for_each(|(name, time)| {
+                let share = if total > 0.0 {
+                    time.as_secs_f64() / total * 100.0
+                } else {
+                    0.0
+                };
+                ui.text(format!("{}: {}us ({:.1}%)", name, time.as_micros(), share));
+            });
+            ui.separator();
+            ui.text(format!("Total: {}us", timings.total().as_micros()));
+        });
+}
+
 pub fn make_default_ui(ui: &mut imgui::Ui) {
     // Window
     imgui::Window::new(im_str!("Hello world"))
@@ -0,0 +1,25 @@
+use specs::prelude::*;
+use specs::saveload::SimpleMarker;
+
+use crate::ecs::components::{BodyId, Kinematics, Position, SaveMarker};
+
+// prints where every body ended up as csv
+pub fn print_bodies(world: &World) {
+    let positions = world.read_storage::<Position>();
+    let kinematics = world.read_storage::<Kinematics>();
+    let markers = world.read_storage::<SimpleMarker<SaveMarker>>();
+
+    println!("id,x,y,vel_x,vel_y");
+    (&positions, &kinematics, &markers)
+        .join()
+        .for_each(|(pos, kine, marker)| {
+            println!(
+                "{},{},{},{},{}",
+                BodyId::from(marker).0,
+                pos.0.x,
+                pos.0.y,
+                kine.vel.x,
+                kine.vel.y
+            );
+        });
+}
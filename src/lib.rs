@@ -0,0 +1,27 @@
+#![allow(clippy::type_complexity)]
+
+// The N-body simulation without the ggez/imgui frontend. Everything the window does goes through
+// these modules, Simulation is the short way to step a world from another program.
+
+extern crate specs;
+
+extern crate microprofile;
+
+extern crate ron;
+extern crate serde;
+
+pub mod ecs;
+pub mod physics;
+pub mod presets;
+pub mod saveload;
+pub mod scene;
+mod simulation;
+
+pub use ecs::components::{Point, Vector};
+pub use ecs::{components, resources};
+pub use simulation::{run_headless, Simulation};
+
+// the default for resources::GravityConstant
+pub const G: f32 = 1.2;
+pub const SCREEN_X: f32 = 300.0;
+pub const SCREEN_Y: f32 = 300.0;
@@ -5,71 +5,71 @@ use ggez::*;
 
 extern crate specs;
 use specs::prelude::*;
-use specs::saveload::{MarkedBuilder, SimpleMarker, SimpleMarkerAllocator};
+use specs::saveload::{MarkedBuilder, SimpleMarker};
 
 extern crate ron;
 extern crate serde;
 
+mod args;
+mod headless;
 mod main_state;
-mod saveload;
+mod screenshot;
 use main_state::state::MainState;
 
 extern crate microprofile;
 
-mod ecs;
+// the simulation itself is the library half of the crate, this is the window around it
 use ecs::{
-    components::{
-        AccelGraph, Draw, Kinematics, Mass, Point, Position, Preview, Radius, SaveMarker,
-        SpeedGraph, Trail, Vector, XVelGraph, YVelGraph,
-    },
-    entities::{new_body, Body},
+    components::SaveMarker,
+    entities::new_body,
     resources::{
-        EnableTrails, FollowSelectedBody, MainIterations, NewPreview, Paused, PreviewIterations,
-        RelativeTrails, Resolution, StartPoint, DT,
+        AimMode, AutoSave, ColorDisplayMode, FollowSelectedBody, Fullscreen, Grid, LaunchScale,
+        Lod, MainIterations, Metaballs, MotionArrows, NewPreview, NudgeStep, PauseOnFocusLoss,
+        Paused, PotentialMap, PredictSelected, PreviewIterations, RadiusDisplayMode, Resolution,
+        RewindDepth, ShowCenterOfMass, ShowFps, StartPoint, StatsLog, TimeMode, TrailStyle,
+        TrailTaper, ZoomSmoothing,
     },
     systems::{
-        graph_sys::{AccelGraphSys, SpeedGraphSys, XVelGraphSys, YVelGraphSys},
-        physics_systems::{PhysicsSys, PreviewPhysicsSys},
-        trail_sys::{PreviewTrailSys, TrailSys},
+        graph_sys::{AccelGraphSys, AccelMagGraphSys, SpeedGraphSys, XVelGraphSys, YVelGraphSys},
+        physics_systems::PreviewPhysicsSys,
+        trail_sys::PreviewTrailSys,
     },
+    world::new_world,
+};
+use gravity_v2::{
+    ecs, physics, presets, run_headless, saveload, scene, Point, Vector, G, SCREEN_X, SCREEN_Y,
 };
 
 mod gui;
 use gui::imgui_wrapper::ImGuiWrapper;
 
-const G: f32 = 1.2;
-const SCREEN_X: f32 = 300.0;
-const SCREEN_Y: f32 = 300.0;
-
 fn main() -> GameResult {
-    let (ctx, event_loop) = &mut ggez::ContextBuilder::new("N-body gravity sim", "Mikail Khan")
-        .window_setup(ggez::conf::WindowSetup::default().title("Gravity"))
-        .window_mode(ggez::conf::WindowMode::default().dimensions(600.0, 600.0))
-        .build()
-        .expect("error building context");
-
-    let mut world = World::new();
-
-    world.register::<Position>();
-    world.register::<Preview>();
-    world.register::<Kinematics>();
-    world.register::<Mass>();
-    world.register::<Draw>();
-    world.register::<Radius>();
-    world.register::<Trail>();
-    world.register::<SpeedGraph>();
-    world.register::<XVelGraph>();
-    world.register::<YVelGraph>();
-    world.register::<AccelGraph>();
-    world.register::<SimpleMarker<SaveMarker>>();
-    world.insert(SimpleMarkerAllocator::<SaveMarker>::new());
-
-    // a simple orbit,
-    // [x_pos, y_pos], [x_vel, y_vel], mass, radius
-    let data = vec![
-        new_body([215.0, 100.0], [0.0, -1.1], 0.01, 0.8),
-        new_body([150.0, 100.0], [0.0, 0.0], 75.0, 5.0),
-    ];
+    let args = args::Args::parse();
+    let mut world = new_world();
+
+    // the --scene file if it loads, a random cloud if --bodies is given, otherwise a simple orbit
+    let scene = args
+        .scene
+        .as_ref()
+        .and_then(|path| match scene::load_scene(path) {
+            Ok(scene) => Some(scene),
+            Err(e) => {
+                eprintln!("Error loading scene, using the default instead: {}", e);
+                None
+            }
+        });
+
+    let data = if let Some(scene) = scene {
+        scene.bodies()
+    } else if let Some(count) = args.bodies {
+        presets::random_bodies(count, 0, graphics::Rect::new(0.0, 0.0, SCREEN_X, SCREEN_Y))
+    } else {
+        // [x_pos, y_pos], [x_vel, y_vel], mass, radius
+        vec![
+            new_body([215.0, 100.0], [0.0, -1.1], 0.01, 0.8),
+            new_body([150.0, 100.0], [0.0, 0.0], 75.0, 5.0),
+        ]
+    };
 
     for (pos, kine, mass, draw, rad, trail) in data {
         world
@@ -84,31 +84,69 @@ fn main() -> GameResult {
             .build();
     }
 
+    if let Some(steps) = args.headless {
+        run_headless(&mut world, steps, 1.0);
+        headless::print_bodies(&world);
+        return Ok(());
+    }
+
+    let custom_size = args.width.is_some() || args.height.is_some();
+    let window_size = (args.width.unwrap_or(600.0), args.height.unwrap_or(600.0));
+    let (ctx, event_loop) = &mut ggez::ContextBuilder::new("N-body gravity sim", "Mikail Khan")
+        .window_setup(ggez::conf::WindowSetup::default().title("Gravity"))
+        .window_mode(ggez::conf::WindowMode::default().dimensions(window_size.0, window_size.1))
+        .build()
+        .expect("error building context");
+
     // ggez screen size stuff
     let hidpi_factor = event_loop.get_primary_monitor().get_hidpi_factor() as f32;
-    let dimensions = event_loop.get_primary_monitor().get_dimensions();
-    let dimensions_vec = Vector::new(dimensions.width as f32, dimensions.height as f32);
-    let aspect_ratio = dimensions.height / dimensions.width;
-
-    // initialize all ECS resources and systems
+    // the monitor's size unless the window size was given
+    let dimensions_vec = if custom_size {
+        Vector::new(window_size.0, window_size.1)
+    } else {
+        let dimensions = event_loop.get_primary_monitor().get_dimensions();
+        Vector::new(dimensions.width as f32, dimensions.height as f32)
+    };
+    let aspect_ratio = dimensions_vec.y / dimensions_vec.x;
+
+    // initialize the remaining ECS resources and systems, new_world inserts the physics ones
     world.insert(MainIterations(1));
     world.insert(PreviewIterations(25));
     world.insert(Resolution(dimensions_vec));
-    world.insert(DT(1.0));
     world.insert(Paused(false));
+    world.insert(TimeMode::FixedStep);
+    world.insert(AimMode::Slingshot);
+    world.insert(PauseOnFocusLoss(false));
+    world.insert(ZoomSmoothing(true));
     world.insert(StartPoint(None));
+    world.insert(LaunchScale::default());
+    world.insert(NudgeStep::default());
     world.insert(NewPreview(false));
     world.insert(FollowSelectedBody(false));
-    world.insert(EnableTrails(true));
-    world.insert(RelativeTrails::new());
-
+    world.insert(PredictSelected(false));
+    world.insert(Fullscreen(false));
+    world.insert(Metaballs(false));
+    world.insert(ShowCenterOfMass(false));
+    world.insert(ShowFps(false));
+    world.insert(Lod::default());
+    world.insert(Grid::default());
+    world.insert(MotionArrows::default());
+    world.insert(PotentialMap::default());
+    world.insert(TrailTaper(false));
+    world.insert(TrailStyle::Solid);
+    world.insert(RadiusDisplayMode::True);
+    world.insert(ColorDisplayMode::Draw);
+    world.insert(AutoSave::default());
+    world.insert(StatsLog::default());
+    world.insert(RewindDepth::default());
+
+    // the physics itself is run by physics::do_physics before each dispatch
     let mut main_dispatcher = DispatcherBuilder::new()
-        .with(PhysicsSys, "physics_system", &[])
-        .with(TrailSys, "trail_system", &[])
-        .with(SpeedGraphSys, "speed_graph_system", &["physics_system"])
-        .with(XVelGraphSys, "xvel_graph_system", &["physics_system"])
-        .with(YVelGraphSys, "yvel_graph_system", &["physics_system"])
-        .with(AccelGraphSys, "accel_graph_system", &["physics_system"])
+        .with(SpeedGraphSys, "speed_graph_system", &[])
+        .with(XVelGraphSys, "xvel_graph_system", &[])
+        .with(YVelGraphSys, "yvel_graph_system", &[])
+        .with(AccelGraphSys, "accel_graph_system", &[])
+        .with(AccelMagGraphSys, "accel_mag_graph_system", &[])
         .build();
 
     let mut preview_dispatcher = DispatcherBuilder::new()
@@ -120,13 +158,13 @@ fn main() -> GameResult {
     preview_dispatcher.setup(&mut world);
 
     // set initial screen size and run
-    graphics::set_mode(
-        ctx,
-        ggez::conf::WindowMode::default()
-            .resizable(true)
-            .fullscreen_type(ggez::conf::FullscreenType::Windowed),
-    )
-    .expect("error resizing window");
+    let mut window_mode = ggez::conf::WindowMode::default()
+        .resizable(true)
+        .fullscreen_type(ggez::conf::FullscreenType::Windowed);
+    if custom_size {
+        window_mode = window_mode.dimensions(window_size.0, window_size.1);
+    }
+    graphics::set_mode(ctx, window_mode).expect("error resizing window");
 
     graphics::set_screen_coordinates(
         ctx,
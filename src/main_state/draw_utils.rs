@@ -4,21 +4,212 @@ use ggez::{
     Context, GameResult,
 };
 
+use imgui::ImString;
+
 use specs::prelude::*;
 
+use crate::ecs::systems::physics_systems::gravity_accel;
 use crate::ecs::{
     components::{
-        AccelGraph, Draw, Mass, Position, Preview, Radius, SpeedGraph, Trail, XVelGraph, YVelGraph,
+        AccelGraph, AccelMagGraph, Anchored, Draw, Kinematics, Label, Mass, Position, Preview,
+        Radius, SpeedGraph, Trail, XVelGraph, YVelGraph,
+    },
+    entities::body_id,
+    resources::{
+        AimMode, AutoSave, BarnesHutTheta, Boundary, CollisionIterations, CollisionLog,
+        CollisionMode, ColorDisplayMode, Density, DiagnosticsLog, GravityConstant, GravityMode,
+        Grid, InspiralDamping, Integrator, LaunchScale, Lod, MainIterations, MaxBodyMass, MaxSpeed,
+        MergeThreshold, MotionArrows, NanPolicy, NudgeStep, PauseOnFocusLoss, PhysicsTimings,
+        PotentialMap, PreviewIterations, RadiusDisplayMode, Resolution, RewindDepth,
+        SelectedEntity, Softening, StartPoint, StatsLog, StickThreshold, Substeps, SystemStats,
+        TimeMode, TrailScope, TrailStyle, TrailTaper, WorldSize, ZoomSmoothing, DT,
     },
-    resources::{MainIterations, PreviewIterations, Resolution, StartPoint, DT},
     systems::graph_sys::GraphType,
 };
-use crate::Point;
+use crate::gui::imgui_wrapper::{Tool, UiChoice};
+use crate::physics::{center_of_mass, is_bound, predict_path, update_stats};
+use crate::{Point, Vector};
 
+use crate::main_state::field::FieldGrid;
 use crate::main_state::state::{scale_pos, MainState};
 
-static TRAIL_COLOR: graphics::Color = graphics::Color::new(0.25, 0.45, 1.0, 1.0);
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+
 static OUTLINE_COLOR: graphics::Color = graphics::Color::new(1.0, 0.1, 0.05, 1.0);
+static PERTURBED_COLOR: graphics::Color = graphics::Color::new(1.0, 0.55, 0.1, 1.0);
+static MEASURE_COLOR: graphics::Color = graphics::Color::new(1.0, 1.0, 0.3, 1.0);
+static GRID_COLOR: graphics::Color = graphics::Color::new(1.0, 1.0, 1.0, 0.08);
+static AXIS_COLOR: graphics::Color = graphics::Color::new(1.0, 1.0, 1.0, 0.25);
+static BOUNDARY_COLOR: graphics::Color = graphics::Color::new(0.5, 0.5, 0.5, 0.8);
+static PREDICTION_COLOR: graphics::Color = graphics::Color::new(0.1, 1.0, 0.2, 0.8);
+static PREVIEW_COLLISION_COLOR: graphics::Color = graphics::Color::new(1.0, 0.15, 0.1, 0.9);
+static VELOCITY_ARROW_COLOR: graphics::Color = graphics::Color::new(0.3, 0.9, 1.0, 0.9);
+static ACCEL_ARROW_COLOR: graphics::Color = graphics::Color::new(1.0, 0.8, 0.2, 0.9);
+static CENTER_OF_MASS_COLOR: graphics::Color = graphics::Color::new(1.0, 0.2, 0.8, 0.9);
+static METABALL_COLOR: graphics::Color = graphics::Color::new(0.6, 0.85, 1.0, 0.9);
+
+// fraction of the way the camera moves towards a followed body each frame
+const FOLLOW_EASE: f32 = 0.2;
+
+// number of field samples across the width of the screen, the cost is samples * bodies
+const METABALL_RESOLUTION: usize = 96;
+
+// the potential map is only resampled this often unless the camera moves
+const POTENTIAL_MAP_REFRESH_FRAMES: usize = 5;
+
+// most grid lines drawn across the screen, the spacing is doubled until it's under this
+const MAX_GRID_LINES: f32 = 100.0;
+
+// most segments in a tapered trail, longer trails skip points to stay under it
+const TAPER_MAX_SEGMENTS: usize = 128;
+
+// A faded trail is drawn in this many steps of alpha rather than one per segment, so long trails
+// cost about as much as solid ones.
+const TRAIL_FADE_BANDS: usize = 16;
+
+// Triangles covering a trail whose width shrinks from full width at the body, the newest point,
+// to nothing at the tail.
+fn taper_triangles(points: &VecDeque<Point>, width: f32) -> Vec<Point> {
+    if points.len() < 2 {
+        return Vec::new();
+    }
+
+    let stride = (points.len() - 1) / TAPER_MAX_SEGMENTS + 1;
+    let mut sampled: Vec<(f32, Point)> = points
+        .iter()
+        .enumerate()
+        .step_by(stride)
+        .map(|(i, p)| (i as f32 / (points.len() - 1) as f32, *p))
+        .collect();
+    // the newest point is always included so the trail reaches the body
+    if (points.len() - 1) % stride != 0 {
+        sampled.push((1.0, points[points.len() - 1]));
+    }
+
+    let mut triangles = Vec::with_capacity(sampled.len() * 6);
+    sampled.windows(2).for_each(|segment| {
+        let (t1, p1) = segment[0];
+        let (t2, p2) = segment[1];
+        let dir = p2 - p1;
+        let len = dir.norm();
+        if len == 0.0 {
+            return;
+        }
+        let normal = Vector::new(-dir.y, dir.x) / len;
+
+        let (w1, w2) = (normal * width * t1 / 2.0, normal * width * t2 / 2.0);
+        let (a, b, c, d) = (p1 + w1, p1 - w1, p2 + w2, p2 - w2);
+        triangles.extend_from_slice(&[a, b, c, b, d, c]);
+    });
+
+    triangles
+}
+
+// Splits segments, oldest first, into at most TRAIL_FADE_BANDS runs, each with the fraction of the
+// trail's alpha it's drawn with. The newest run is fully opaque.
+fn fade_bands(segments: usize) -> Vec<(Range<usize>, f32)> {
+    let bands = segments.min(TRAIL_FADE_BANDS);
+    (0..bands)
+        .map(|band| {
+            let range = band * segments / bands..(band + 1) * segments / bands;
+            (range, (band + 1) as f32 / bands as f32)
+        })
+        .collect()
+}
+
+fn faded(color: Color, alpha: f32) -> Color {
+    Color {
+        a: color.a * alpha,
+        ..color
+    }
+}
+
+// A line from start along arrow with a triangle head at the end. The head is at most half the
+// arrow so short ones are still arrows.
+fn draw_arrow(
+    builder: &mut MeshBuilder,
+    start: Point,
+    arrow: Vector,
+    line_width: f32,
+    head_len: f32,
+    color: Color,
+) {
+    let len = arrow.norm();
+    if len == 0.0 || !len.is_finite() {
+        return;
+    }
+
+    let dir = arrow / len;
+    let normal = Vector::new(-dir.y, dir.x);
+    let head_len = head_len.min(len / 2.0);
+    let tip = start + arrow;
+    let base = tip - dir * head_len;
+
+    if let Err(e) = builder.line(&[start, base], line_width, color) {
+        dbg!(e);
+    }
+    let head = [
+        tip,
+        base + normal * head_len * 0.5,
+        base - normal * head_len * 0.5,
+    ];
+    if let Err(e) = builder.triangles(&head, color) {
+        dbg!(e);
+    }
+}
+
+// blue for still bodies through to red at max_speed
+fn speed_color(speed: f32, max_speed: f32) -> Color {
+    let t = if max_speed > 0.0 {
+        (speed / max_speed).min(1.0)
+    } else {
+        0.0
+    };
+    Color::new(0.2 + 0.8 * t, 0.4 - 0.2 * t, 1.0 - 0.9 * t, 1.0)
+}
+
+// The bodies in one LOD cell, drawn as a single dot at their average position. The dot has the
+// combined area of the bodies but is at least a fraction of a cell so sparse cells stay visible.
+struct LodBucket {
+    count: usize,
+    pos_sum: Vector,
+    area_sum: f32,
+    color_sum: [f32; 4],
+}
+
+impl LodBucket {
+    fn new() -> Self {
+        LodBucket {
+            count: 0,
+            pos_sum: Vector::new(0.0, 0.0),
+            area_sum: 0.0,
+            color_sum: [0.0; 4],
+        }
+    }
+
+    fn add(&mut self, pos: Point, rad: f32, color: Color) {
+        self.count += 1;
+        self.pos_sum += pos.coords;
+        self.area_sum += rad * rad;
+        self.color_sum[0] += color.r;
+        self.color_sum[1] += color.g;
+        self.color_sum[2] += color.b;
+        self.color_sum[3] += color.a;
+    }
+
+    fn dot(&self, cell_size: f32) -> (Point, f32, Color) {
+        let n = self.count as f32;
+        let rad = self.area_sum.sqrt().max(cell_size * 0.25).min(cell_size);
+        let color = Color::new(
+            self.color_sum[0] / n,
+            self.color_sum[1] / n,
+            self.color_sum[2] / n,
+            self.color_sum[3] / n,
+        );
+        (Point::from(self.pos_sum / n), rad, color)
+    }
+}
 
 impl<'a, 'b> MainState<'a, 'b> {
     pub fn follow_selected_body(&mut self, ctx: &mut Context) -> GameResult {
@@ -27,45 +218,188 @@ impl<'a, 'b> MainState<'a, 'b> {
             let mut screen_coordinates = ggez::graphics::screen_coordinates(ctx);
             let positions = self.world.read_storage::<Position>();
 
+            // only the center moves so the zoom is kept
             let followed_pos = positions.get(e).unwrap();
-            screen_coordinates.x = followed_pos.0.x - screen_coordinates.w / 2.0;
-            screen_coordinates.y = followed_pos.0.y - screen_coordinates.h / 2.0;
+            let center_x = screen_coordinates.x + screen_coordinates.w / 2.0;
+            let center_y = screen_coordinates.y + screen_coordinates.h / 2.0;
+            screen_coordinates.x += (followed_pos.0.x - center_x) * FOLLOW_EASE;
+            screen_coordinates.y += (followed_pos.0.y - center_y) * FOLLOW_EASE;
             ggez::graphics::set_screen_coordinates(ctx, screen_coordinates)?;
         }
         Ok(())
     }
 
     pub fn update_gui_data(&mut self) {
+        self.imgui_wrapper.render_data.tool = self.tool;
+        self.imgui_wrapper.render_data.recording = self.recording.is_some();
+
         if let Some(e) = self.selected_entity {
             let masses = self.world.read_storage::<Mass>();
             let radii = self.world.read_storage::<Radius>();
             let trails = self.world.read_storage::<Trail>();
+            let draws = self.world.read_storage::<Draw>();
+            let positions = self.world.read_storage::<Position>();
+            let kinematics = self.world.read_storage::<Kinematics>();
+            let labels = self.world.read_storage::<Label>();
+            let anchors = self.world.read_storage::<Anchored>();
             self.imgui_wrapper.render_data.mass = masses.get(e).unwrap().0;
             self.imgui_wrapper.render_data.rad = radii.get(e).unwrap().0;
+            self.imgui_wrapper.render_data.color = draws.get(e).unwrap().0.into();
             self.imgui_wrapper.render_data.trail_len = trails.get(e).unwrap().max_len;
+            self.imgui_wrapper.render_data.trail_enabled = trails.get(e).unwrap().enabled;
+            self.imgui_wrapper.render_data.trail_color = trails.get(e).unwrap().color.into();
+            self.imgui_wrapper.render_data.label =
+                ImString::new(labels.get(e).map_or("", |label| label.0.as_str()));
+            self.imgui_wrapper.render_data.anchored = anchors.contains(e);
+
+            let pos = positions.get(e).unwrap().0;
+            let kine = kinematics.get(e).unwrap();
+            self.imgui_wrapper.render_data.pos = [pos.x, pos.y];
+            self.imgui_wrapper.render_data.vel = [kine.vel.x, kine.vel.y];
+            self.imgui_wrapper.render_data.accel_mag = kine.accel.norm();
+
+            let (bound, escape_speed) = is_bound(&self.world, e);
+            self.imgui_wrapper.render_data.bound = bound;
+            self.imgui_wrapper.render_data.escape_speed = escape_speed;
+        }
+        self.imgui_wrapper.render_data.body_id =
+            self.selected_entity.and_then(|e| body_id(&self.world, e));
+
+        if self.imgui_wrapper.shown_menus.contains(&UiChoice::Stats) {
+            update_stats(&mut self.world);
+            self.imgui_wrapper.render_data.stats = *self.world.fetch::<SystemStats>();
+            self.imgui_wrapper.render_data.collision_log = self
+                .world
+                .fetch::<CollisionLog>()
+                .events
+                .iter()
+                .rev()
+                .map(|event| {
+                    format!(
+                        "{} + {}: {:.4} lost",
+                        event.bodies.0, event.bodies.1, event.dissipated
+                    )
+                })
+                .collect();
+            self.imgui_wrapper.render_data.diagnostics_log = self
+                .world
+                .fetch::<DiagnosticsLog>()
+                .events
+                .iter()
+                .rev()
+                .map(|event| format!("{}: {:?}", event.body, event.policy))
+                .collect();
+        }
+
+        // the phases are only timed while the window is open
+        let timings_shown = self.imgui_wrapper.shown_menus.contains(&UiChoice::Timings);
+        self.world.fetch_mut::<PhysicsTimings>().enabled = timings_shown;
+        if timings_shown {
+            self.imgui_wrapper.render_data.timings = *self.world.fetch::<PhysicsTimings>();
         }
     }
 
     pub fn draw_trails(&self, builder: &mut MeshBuilder) {
         let trails = self.world.read_storage::<Trail>();
         let radii = self.world.read_storage::<Radius>();
+        let previews = self.world.read_storage::<Preview>();
+        let entities = self.world.entities();
+        let trail_scope = *self.world.fetch::<TrailScope>();
+        let preview_collision = self.preview_collision;
+        let selected_entity = SelectedEntity(self.selected_entity);
+        let taper = self.world.fetch::<TrailTaper>().0;
+        let fade = *self.world.fetch::<TrailStyle>() == TrailStyle::Fade;
 
-        (&trails, &radii).join().for_each(|(trail, radius)| {
-            let slices = trail.points.as_slices();
-            if slices.0.len() >= 2 {
-                if let Err(e) = builder.line(slices.0, 0.25 * radius.0, TRAIL_COLOR) {
-                    dbg!(e);
-                };
-            }
-            if slices.1.len() >= 2 {
-                if let Err(e) = builder.line(slices.1, 0.25 * radius.0, TRAIL_COLOR) {
-                    dbg!(e);
-                };
-            }
-        });
+        (&trails, &radii, &entities)
+            .join()
+            .for_each(|(trail, radius, entity)| {
+                let is_preview = previews.get(entity).is_some();
+                // the preview's trail is its prediction so it's always drawn
+                if !is_preview && (!trail.enabled || !trail_scope.includes(entity, selected_entity))
+                {
+                    return;
+                }
+
+                // past where the preview hits a body its trail is red, with a ring where it hit
+                if let (true, Some(i)) = (is_preview, preview_collision) {
+                    let points: Vec<Point> = trail.points.iter().cloned().collect();
+                    let i = i.min(points.len().saturating_sub(1));
+                    if i >= 1 {
+                        if let Err(e) = builder.line(&points[..=i], 0.25 * radius.0, trail.color) {
+                            dbg!(e);
+                        };
+                    }
+                    if points.len() >= i + 2 {
+                        if let Err(e) =
+                            builder.line(&points[i..], 0.25 * radius.0, PREVIEW_COLLISION_COLOR)
+                        {
+                            dbg!(e);
+                        };
+                    }
+                    if let Some(hit) = points.get(i) {
+                        let hit: ggez::mint::Point2<f32> = [hit.x, hit.y].into();
+                        builder.circle(
+                            DrawMode::stroke(0.25 * radius.0),
+                            hit,
+                            radius.0,
+                            0.01,
+                            PREVIEW_COLLISION_COLOR,
+                        );
+                    }
+                    return;
+                }
+
+                // the preview's trail starts at the body so it isn't tapered or faded
+                if taper && !is_preview {
+                    let triangles = taper_triangles(&trail.points, 0.25 * radius.0);
+                    if triangles.is_empty() {
+                        return;
+                    }
+                    // six vertices per segment
+                    let bands = if fade {
+                        fade_bands(triangles.len() / 6)
+                    } else {
+                        vec![(0..triangles.len() / 6, 1.0)]
+                    };
+                    bands.into_iter().for_each(|(range, alpha)| {
+                        let band = &triangles[range.start * 6..range.end * 6];
+                        if let Err(e) = builder.triangles(band, faded(trail.color, alpha)) {
+                            dbg!(e);
+                        };
+                    });
+                    return;
+                }
+
+                if fade && !is_preview {
+                    let points: Vec<Point> = trail.points.iter().cloned().collect();
+                    let segments = points.len().saturating_sub(1);
+                    // neighbouring bands share a point so the line is unbroken
+                    fade_bands(segments).into_iter().for_each(|(range, alpha)| {
+                        let band = &points[range.start..=range.end];
+                        if let Err(e) =
+                            builder.line(band, 0.25 * radius.0, faded(trail.color, alpha))
+                        {
+                            dbg!(e);
+                        };
+                    });
+                    return;
+                }
+
+                let slices = trail.points.as_slices();
+                if slices.0.len() >= 2 {
+                    if let Err(e) = builder.line(slices.0, 0.25 * radius.0, trail.color) {
+                        dbg!(e);
+                    };
+                }
+                if slices.1.len() >= 2 {
+                    if let Err(e) = builder.line(slices.1, 0.25 * radius.0, trail.color) {
+                        dbg!(e);
+                    };
+                }
+            });
     }
 
-    pub fn draw_bodies(&self, builder: &mut MeshBuilder) {
+    pub fn draw_bodies(&self, builder: &mut MeshBuilder, ctx: &mut Context) {
         // this is kind of inelegant but previews don't have the Draw component and color is
         // hardcoded
         // TODO?
@@ -73,21 +407,152 @@ impl<'a, 'b> MainState<'a, 'b> {
         let positions = self.world.read_storage::<Position>();
         let radii = self.world.read_storage::<Radius>();
         let previews = self.world.read_storage::<Preview>();
+        let kinematics = self.world.read_storage::<Kinematics>();
+        let radius_mode = *self.world.fetch::<RadiusDisplayMode>();
+        let color_mode = *self.world.fetch::<ColorDisplayMode>();
+        let lod = *self.world.fetch::<Lod>();
 
-        (&draws, &positions, &radii)
+        // the speed that's drawn fully red, a scan over every body each frame so O(n)
+        let max_speed = match color_mode {
+            ColorDisplayMode::Draw => 0.0,
+            ColorDisplayMode::Speed => (&draws, &kinematics)
+                .join()
+                .map(|(_, kine)| kine.vel.norm())
+                .fold(0.0, f32::max),
+        };
+
+        // the size of a LOD cell in world units, bodies smaller than a cell are grouped
+        let cell_size = {
+            let resolution = self.world.fetch::<Resolution>().0;
+            let coords = ggez::graphics::screen_coordinates(ctx);
+            lod.cell_pixels * coords.w / resolution.x
+        };
+        let mut buckets: HashMap<(i32, i32), LodBucket> = HashMap::new();
+
+        (&draws, &positions, &radii, kinematics.maybe())
             .join()
-            .for_each(|(color, pos, rad)| {
-                let point: ggez::mint::Point2<f32> = (*pos).into();
-                builder.circle(DrawMode::fill(), point, rad.0, 0.01, color.0);
+            .for_each(|(draw, pos, rad, kine)| {
+                let color = match (color_mode, kine) {
+                    (ColorDisplayMode::Speed, Some(kine)) => {
+                        speed_color(kine.vel.norm(), max_speed)
+                    }
+                    _ => draw.0,
+                };
+                let rad = radius_mode.display_radius(rad.0);
+                if lod.enabled && cell_size > 0.0 && rad.abs() < cell_size {
+                    let cell = (
+                        (pos.0.x / cell_size).floor() as i32,
+                        (pos.0.y / cell_size).floor() as i32,
+                    );
+                    buckets
+                        .entry(cell)
+                        .or_insert_with(LodBucket::new)
+                        .add(pos.0, rad, color);
+                } else {
+                    let point: ggez::mint::Point2<f32> = (*pos).into();
+                    builder.circle(DrawMode::fill(), point, rad, 0.01, color);
+                }
             });
 
+        buckets.values().for_each(|bucket| {
+            let (pos, rad, color) = bucket.dot(cell_size);
+            builder.circle(DrawMode::fill(), pos, rad, 0.05, color);
+        });
+
         (&previews, &positions, &radii)
             .join()
             .for_each(|(_, pos, rad)| {
                 let point: ggez::mint::Point2<f32> = (*pos).into();
                 let color = Color::new(0.1, 1.0, 0.2, 1.0);
-                builder.circle(DrawMode::fill(), point, rad.0, 0.05, color);
+                let rad = radius_mode.display_radius(rad.0);
+                builder.circle(DrawMode::fill(), point, rad, 0.05, color);
+            });
+    }
+
+    // the perturbed copy's bodies and trails, all in one contrasting color
+    pub fn draw_perturbed(&self, builder: &mut MeshBuilder) {
+        if let Some(perturbed_world) = &self.perturbed_world {
+            let positions = perturbed_world.read_storage::<Position>();
+            let radii = perturbed_world.read_storage::<Radius>();
+            let trails = perturbed_world.read_storage::<Trail>();
+            let radius_mode = *self.world.fetch::<RadiusDisplayMode>();
+
+            (&trails, &radii).join().for_each(|(trail, radius)| {
+                let slices = trail.points.as_slices();
+                if slices.0.len() >= 2 {
+                    if let Err(e) = builder.line(slices.0, 0.25 * radius.0, PERTURBED_COLOR) {
+                        dbg!(e);
+                    };
+                }
+                if slices.1.len() >= 2 {
+                    if let Err(e) = builder.line(slices.1, 0.25 * radius.0, PERTURBED_COLOR) {
+                        dbg!(e);
+                    };
+                }
             });
+
+            (&positions, &radii).join().for_each(|(pos, rad)| {
+                let point: ggez::mint::Point2<f32> = (*pos).into();
+                let rad = radius_mode.display_radius(rad.0);
+                builder.circle(DrawMode::fill(), point, rad, 0.01, PERTURBED_COLOR);
+            });
+        }
+    }
+
+    // Outlines the regions where the summed falloff r^2 / d^2 of every body is above 1, so bodies
+    // that are close together are drawn as a single blob. This is only drawn over the bodies and
+    // doesn't change the physics.
+    pub fn draw_metaballs(&self, builder: &mut MeshBuilder, ctx: &mut Context) {
+        let positions = self.world.read_storage::<Position>();
+        let radii = self.world.read_storage::<Radius>();
+        let previews = self.world.read_storage::<Preview>();
+
+        let bodies: Vec<(Point, f32)> = (&positions, &radii, !&previews)
+            .join()
+            .map(|(pos, rad, _)| (pos.0, rad.0.powi(2)))
+            .collect();
+        if bodies.is_empty() {
+            return;
+        }
+
+        let screen_coords = graphics::screen_coordinates(ctx);
+        let rows = (METABALL_RESOLUTION as f32 * screen_coords.h / screen_coords.w) as usize;
+        let grid = FieldGrid::sample(screen_coords, METABALL_RESOLUTION, rows, |p| {
+            bodies
+                .iter()
+                .map(|&(center, rad_sqr)| {
+                    rad_sqr / (p - center).norm_squared().max(std::f32::EPSILON)
+                })
+                .sum()
+        });
+
+        let line_width = screen_coords.w * 0.002;
+        grid.contour(1.0).iter().for_each(|segment| {
+            if segment[0] != segment[1] {
+                if let Err(e) = builder.line(&segment[..], line_width, METABALL_COLOR) {
+                    dbg!(e);
+                }
+            }
+        });
+    }
+
+    // a dashed line along the selected body's predicted path
+    pub fn draw_prediction(&self, builder: &mut MeshBuilder, ctx: &mut Context, entity: Entity) {
+        let steps = self.world.fetch::<PreviewIterations>().0;
+        let mut path = vec![match self.world.read_storage::<Position>().get(entity) {
+            Some(pos) => pos.0,
+            None => return,
+        }];
+        path.extend(predict_path(&self.world, entity, steps));
+
+        let line_width = graphics::screen_coordinates(ctx).w * 0.002;
+        path.windows(2).step_by(2).for_each(|segment| {
+            if segment[0] != segment[1] {
+                if let Err(e) = builder.line(segment, line_width, PREDICTION_COLOR) {
+                    dbg!(e);
+                }
+            }
+        });
     }
 
     pub fn draw_preview(&self, builder: &mut MeshBuilder, ctx: &mut Context) {
@@ -102,11 +567,12 @@ impl<'a, 'b> MainState<'a, 'b> {
             scale_pos(mouse_pos, coords, resolution)
         };
 
-        if self.creating {
+        if self.tool == Tool::Create {
+            let radius_mode = *self.world.fetch::<RadiusDisplayMode>();
             builder.circle(
                 DrawMode::fill(),
                 p,
-                self.imgui_wrapper.render_data.create_rad,
+                radius_mode.display_radius(self.new_body_radius()),
                 0.05,
                 Color::new(1.0, 1.0, 1.0, 0.5),
             );
@@ -122,15 +588,414 @@ impl<'a, 'b> MainState<'a, 'b> {
         }
     }
 
+    // Cells are shaded blue by how deep the potential is compared to the deepest cell on screen,
+    // so flat space is left dark. The samples are cached for a few frames.
+    pub fn draw_potential_map(&mut self, builder: &mut MeshBuilder, ctx: &mut Context) {
+        let potential_map = *self.world.fetch::<PotentialMap>();
+        if !potential_map.enabled {
+            self.potential_map = None;
+            return;
+        }
+
+        let screen_coords = graphics::screen_coordinates(ctx);
+        let cols = potential_map.resolution;
+        let rows = (cols as f32 * screen_coords.h / screen_coords.w) as usize;
+        let stale = match &self.potential_map {
+            Some(grid) => {
+                grid.rect != screen_coords
+                    || grid.cols != cols.max(2)
+                    || ggez::timer::ticks(ctx) % POTENTIAL_MAP_REFRESH_FRAMES == 0
+            }
+            None => true,
+        };
+
+        if stale {
+            let positions = self.world.read_storage::<Position>();
+            let masses = self.world.read_storage::<Mass>();
+            let g = self.world.fetch::<GravityConstant>().0;
+            let softening = self.world.fetch::<Softening>().0;
+
+            let bodies: Vec<(Point, f32)> = (&positions, &masses)
+                .join()
+                .map(|(pos, mass)| (pos.0, mass.0))
+                .collect();
+            self.potential_map = Some(FieldGrid::sample(screen_coords, cols, rows, |p| {
+                bodies
+                    .iter()
+                    .map(|&(center, mass)| {
+                        -g * mass / ((p - center).norm_squared() + softening.powi(2)).sqrt()
+                    })
+                    .sum()
+            }));
+        }
+
+        if let Some(grid) = &self.potential_map {
+            let deepest = grid.values.iter().cloned().fold(0.0, f32::min);
+            if deepest >= 0.0 {
+                return;
+            }
+
+            let cell_size = grid.cell_size();
+            (0..grid.rows).for_each(|row| {
+                (0..grid.cols).for_each(|col| {
+                    // square root so the shallow parts of a well are still visible
+                    let depth = (grid.value(col, row) / deepest).max(0.0).sqrt();
+                    if depth < 0.02 {
+                        return;
+                    }
+
+                    let center = grid.point(col, row);
+                    builder.rectangle(
+                        DrawMode::fill(),
+                        graphics::Rect::new(
+                            center.x - cell_size.x / 2.0,
+                            center.y - cell_size.y / 2.0,
+                            cell_size.x,
+                            cell_size.y,
+                        ),
+                        graphics::Color::new(0.1, 0.3, 1.0, 0.6 * depth),
+                    );
+                });
+            });
+        }
+    }
+
+    // only the lines on screen are added, with the axes brighter than the rest
+    pub fn draw_grid(&self, builder: &mut MeshBuilder, ctx: &mut Context) {
+        let grid = *self.world.fetch::<Grid>();
+        if !grid.enabled || grid.spacing <= 0.0 {
+            return;
+        }
+
+        let coords = graphics::screen_coordinates(ctx);
+        let mut spacing = grid.spacing;
+        while coords.w.max(coords.h) / spacing > MAX_GRID_LINES {
+            spacing *= 2.0;
+        }
+        let width = coords.w * 0.001;
+
+        // lines are counted from the axis so line 0 is exactly on it
+        let first_x = (coords.left() / spacing).ceil() as i64;
+        let last_x = (coords.right() / spacing).floor() as i64;
+        for i in first_x..=last_x {
+            let x = i as f32 * spacing;
+            let color = if i == 0 { AXIS_COLOR } else { GRID_COLOR };
+            builder
+                .line(
+                    &[Point::new(x, coords.top()), Point::new(x, coords.bottom())],
+                    width,
+                    color,
+                )
+                .expect("not enough points in line");
+        }
+
+        let first_y = (coords.top() / spacing).ceil() as i64;
+        let last_y = (coords.bottom() / spacing).floor() as i64;
+        for i in first_y..=last_y {
+            let y = i as f32 * spacing;
+            let color = if i == 0 { AXIS_COLOR } else { GRID_COLOR };
+            builder
+                .line(
+                    &[Point::new(coords.left(), y), Point::new(coords.right(), y)],
+                    width,
+                    color,
+                )
+                .expect("not enough points in line");
+        }
+    }
+
+    // drawn in screen pixels below the menu bar whatever the zoom
+    pub fn draw_fps(&self, ctx: &mut Context) {
+        let coords = graphics::screen_coordinates(ctx);
+        let resolution = self.world.fetch::<Resolution>().0;
+        // previews don't have a mass so they aren't counted
+        let bodies = self.world.read_storage::<Mass>().join().count();
+
+        let text = graphics::Text::new(format!(
+            "{:.0} FPS, {} bodies",
+            ggez::timer::fps(ctx),
+            bodies
+        ));
+        let scale = coords.w / resolution.x;
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::new()
+                .dest(scale_pos([10.0, 35.0], coords, resolution))
+                .scale([scale, scale]),
+        )
+        .expect("error drawing fps");
+    }
+
+    // each labeled body's name just above and to the right of it, the text stays the same size on
+    // screen at any zoom
+    pub fn draw_labels(&self, ctx: &mut Context) {
+        let coords = graphics::screen_coordinates(ctx);
+        let resolution = self.world.fetch::<Resolution>().0;
+        let scale = coords.w / resolution.x;
+        let radius_mode = *self.world.fetch::<RadiusDisplayMode>();
+
+        let positions = self.world.read_storage::<Position>();
+        let radii = self.world.read_storage::<Radius>();
+        let labels = self.world.read_storage::<Label>();
+        (&positions, &radii, &labels)
+            .join()
+            .for_each(|(pos, rad, label)| {
+                // next to the circle as it's drawn, which isn't rad with the log radius mode
+                let rad = radius_mode.display_radius(rad.0);
+                let text = graphics::Text::new(label.0.as_str());
+                let height = text.height(ctx) as f32 * scale;
+                graphics::draw(
+                    ctx,
+                    &text,
+                    graphics::DrawParam::new()
+                        .dest(pos.0 + Vector::new(rad, -rad - height))
+                        .scale([scale, scale]),
+                )
+                .expect("error drawing label");
+            });
+    }
+
+    // outlines the box bodies are kept in, if there is one
+    pub fn draw_boundary(&self, builder: &mut MeshBuilder, ctx: &mut Context) {
+        if *self.world.fetch::<Boundary>() != Boundary::None {
+            let size = self.world.fetch::<WorldSize>().0;
+            let width = graphics::screen_coordinates(ctx).w * 0.002;
+            builder.rectangle(
+                DrawMode::stroke(width),
+                graphics::Rect::new(0.0, 0.0, size.x, size.y),
+                BOUNDARY_COLOR,
+            );
+        }
+    }
+
+    // A crosshair in a ring at the center of mass, drawn the same size on screen at any zoom. It's
+    // only drawn, it isn't an entity so it can't be selected.
+    pub fn draw_center_of_mass(&self, builder: &mut MeshBuilder, ctx: &mut Context) {
+        if let Some(com) = center_of_mass(&self.world) {
+            let size = graphics::screen_coordinates(ctx).w * 0.015;
+            let width = size * 0.15;
+
+            builder.circle(
+                DrawMode::stroke(width),
+                com,
+                size * 0.6,
+                0.005,
+                CENTER_OF_MASS_COLOR,
+            );
+            builder
+                .line(
+                    &[com - Vector::new(size, 0.0), com + Vector::new(size, 0.0)],
+                    width,
+                    CENTER_OF_MASS_COLOR,
+                )
+                .expect("not enough points in line");
+            builder
+                .line(
+                    &[com - Vector::new(0.0, size), com + Vector::new(0.0, size)],
+                    width,
+                    CENTER_OF_MASS_COLOR,
+                )
+                .expect("not enough points in line");
+        }
+    }
+
+    // The ends of the measurement being dragged, from where it started to the mouse. Each end is
+    // snapped to the body under it if there is one.
+    fn measurement_ends(
+        &self,
+        ctx: &Context,
+    ) -> Option<((Point, Option<Entity>), (Point, Option<Entity>))> {
+        let start = self.measure_start?;
+        let positions = self.world.read_storage::<Position>();
+        let resolution = self.world.fetch::<Resolution>().0;
+        let coords = ggez::graphics::screen_coordinates(ctx);
+        let mouse_pos = ggez::input::mouse::position(ctx);
+
+        let start = match self.measure_start_body {
+            Some(e) => positions.get(e).map(|pos| (pos.0, Some(e))),
+            None => None,
+        }
+        .unwrap_or((start, None));
+        let end = match self.body_at(ctx, mouse_pos.x, mouse_pos.y) {
+            Some(e) if Some(e) != start.1 => positions.get(e).map(|pos| (pos.0, Some(e))),
+            _ => None,
+        }
+        .unwrap_or((scale_pos(mouse_pos, coords, resolution), None));
+
+        Some((start, end))
+    }
+
+    // The distance being measured, and between two bodies how fast they're moving relative to each
+    // other and the pull between them with the same formula as the physics.
+    pub fn measurement_readout(&self, ctx: &Context) -> Option<String> {
+        let ((start, start_body), (end, end_body)) = self.measurement_ends(ctx)?;
+        let mut readout = format!("Distance: {:.3}", (end - start).norm());
+
+        if let (Some(e1), Some(e2)) = (start_body, end_body) {
+            let kinematics = self.world.read_storage::<Kinematics>();
+            let masses = self.world.read_storage::<Mass>();
+            if let (Some(k1), Some(k2), Some(m1), Some(m2)) = (
+                kinematics.get(e1),
+                kinematics.get(e2),
+                masses.get(e1),
+                masses.get(e2),
+            ) {
+                let g = self.world.fetch::<GravityConstant>().0;
+                let softening = self.world.fetch::<Softening>().0;
+                let force = gravity_accel(end - start, m2.0, g, softening).norm() * m1.0;
+                readout += &format!(
+                    "\nRelative Speed: {:.3}\nForce: {:.4}",
+                    (k1.vel - k2.vel).norm(),
+                    force
+                );
+            }
+        }
+
+        Some(readout)
+    }
+
+    // a line from where the measurement started to the mouse
+    pub fn draw_measurement(&self, builder: &mut MeshBuilder, ctx: &mut Context) {
+        if let Some(((start, _), (end, _))) = self.measurement_ends(ctx) {
+            if start != end {
+                builder
+                    .line(&[start, end], 0.5, MEASURE_COLOR)
+                    .expect("not enough points in line");
+            }
+        }
+    }
+
+    // the measurement's readout next to the mouse while it's dragged
+    pub fn draw_measurement_readout(&self, ctx: &mut Context) {
+        if let Some(readout) = self.measurement_readout(ctx) {
+            let coords = graphics::screen_coordinates(ctx);
+            let resolution = self.world.fetch::<Resolution>().0;
+            let scale = coords.w / resolution.x;
+            let mouse_pos = scale_pos(ggez::input::mouse::position(ctx), coords, resolution);
+
+            let text = graphics::Text::new(readout);
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::new()
+                    .dest(mouse_pos + Vector::new(12.0, 12.0) * scale)
+                    .scale([scale, scale])
+                    .color(MEASURE_COLOR),
+            )
+            .expect("error drawing measurement");
+        }
+    }
+
     pub fn update_sim_data(&mut self) {
         self.world
             .insert::<DT>(DT(self.imgui_wrapper.render_data.dt));
+        self.world
+            .insert::<TimeMode>(self.imgui_wrapper.render_data.time_mode);
         self.world.insert::<MainIterations>(MainIterations(
             self.imgui_wrapper.render_data.num_iterations,
         ));
         self.world.insert::<PreviewIterations>(PreviewIterations(
             self.imgui_wrapper.render_data.preview_iterations,
         ));
+        self.world
+            .insert::<CollisionIterations>(CollisionIterations(
+                self.imgui_wrapper.render_data.collision_iterations,
+            ));
+        self.world
+            .insert::<Substeps>(Substeps(self.imgui_wrapper.render_data.substeps as u32));
+        self.world
+            .insert::<LaunchScale>(LaunchScale(self.imgui_wrapper.render_data.launch_scale));
+        self.world
+            .insert::<NudgeStep>(NudgeStep(self.imgui_wrapper.render_data.nudge_step));
+        self.world
+            .insert::<AimMode>(self.imgui_wrapper.render_data.aim_mode);
+        self.world
+            .insert::<GravityConstant>(GravityConstant(self.imgui_wrapper.render_data.g));
+        self.world
+            .insert::<Softening>(Softening(self.imgui_wrapper.render_data.softening));
+        self.world
+            .insert::<Integrator>(self.imgui_wrapper.render_data.integrator);
+        self.world
+            .insert::<GravityMode>(self.imgui_wrapper.render_data.gravity_mode);
+        self.world.insert::<BarnesHutTheta>(BarnesHutTheta(
+            self.imgui_wrapper.render_data.barnes_hut_theta,
+        ));
+        self.world
+            .insert::<CollisionMode>(self.imgui_wrapper.render_data.collision_mode);
+        self.world.insert::<MergeThreshold>(MergeThreshold(
+            self.imgui_wrapper.render_data.merge_threshold,
+        ));
+        self.world
+            .insert::<Boundary>(self.imgui_wrapper.render_data.boundary);
+        self.world
+            .insert::<NanPolicy>(self.imgui_wrapper.render_data.nan_policy);
+        self.world
+            .insert::<WorldSize>(WorldSize(self.imgui_wrapper.render_data.world_size.into()));
+        self.world.insert::<InspiralDamping>(InspiralDamping(
+            self.imgui_wrapper.render_data.inspiral_damping,
+        ));
+        self.world
+            .insert::<Density>(Density(if self.imgui_wrapper.render_data.density_enabled {
+                Some(self.imgui_wrapper.render_data.density)
+            } else {
+                None
+            }));
+        self.world.insert::<MaxSpeed>(MaxSpeed(
+            if self.imgui_wrapper.render_data.max_speed_enabled {
+                Some(self.imgui_wrapper.render_data.max_speed)
+            } else {
+                None
+            },
+        ));
+        self.world.insert::<StickThreshold>(StickThreshold(
+            if self.imgui_wrapper.render_data.stick_threshold_enabled {
+                Some(self.imgui_wrapper.render_data.stick_threshold)
+            } else {
+                None
+            },
+        ));
+        self.world.insert::<MaxBodyMass>(MaxBodyMass(
+            if self.imgui_wrapper.render_data.max_mass_enabled {
+                Some(self.imgui_wrapper.render_data.max_mass)
+            } else {
+                None
+            },
+        ));
+        self.world.insert::<PauseOnFocusLoss>(PauseOnFocusLoss(
+            self.imgui_wrapper.render_data.pause_on_focus_loss,
+        ));
+        self.world
+            .insert::<ZoomSmoothing>(ZoomSmoothing(self.imgui_wrapper.render_data.zoom_smoothing));
+        self.world.insert::<Grid>(Grid {
+            enabled: self.imgui_wrapper.render_data.grid_enabled,
+            spacing: self.imgui_wrapper.render_data.grid_spacing,
+        });
+        self.world.insert::<MotionArrows>(MotionArrows {
+            enabled: self.imgui_wrapper.render_data.motion_arrows_enabled,
+            scale: self.imgui_wrapper.render_data.motion_arrow_scale,
+        });
+        self.world.insert::<PotentialMap>(PotentialMap {
+            enabled: self.imgui_wrapper.render_data.potential_map_enabled,
+            resolution: self.imgui_wrapper.render_data.potential_map_resolution,
+        });
+        self.world.insert::<Lod>(Lod {
+            enabled: self.imgui_wrapper.render_data.lod_enabled,
+            cell_pixels: self.imgui_wrapper.render_data.lod_cell_pixels,
+        });
+        self.world.insert::<StatsLog>(StatsLog {
+            enabled: self.imgui_wrapper.render_data.stats_log_enabled,
+            interval_steps: self.imgui_wrapper.render_data.stats_log_steps,
+            max_rows: self.imgui_wrapper.render_data.stats_log_max_rows,
+        });
+        self.world
+            .insert::<RewindDepth>(RewindDepth(self.imgui_wrapper.render_data.rewind_depth));
+        self.world.insert::<AutoSave>(AutoSave {
+            enabled: self.imgui_wrapper.render_data.autosave_enabled,
+            interval_steps: self.imgui_wrapper.render_data.autosave_steps,
+            interval_mins: self.imgui_wrapper.render_data.autosave_mins,
+            keep: self.imgui_wrapper.render_data.autosave_keep,
+        });
     }
 
     pub fn draw_selected_outline(&self, builder: &mut MeshBuilder, entity: Entity) {
@@ -138,17 +1003,44 @@ impl<'a, 'b> MainState<'a, 'b> {
         let radii = self.world.read_storage::<Radius>();
 
         if let (Some(pos), Some(rad)) = (positions.get(entity), radii.get(entity)) {
-            let stroke_width = (rad.0 * 0.05).max(0.25);
+            let rad = self
+                .world
+                .fetch::<RadiusDisplayMode>()
+                .display_radius(rad.0);
+            let stroke_width = (rad * 0.05).max(0.25);
             builder.circle(
                 DrawMode::stroke(stroke_width),
                 pos.0,
-                rad.0 + stroke_width / 2.0,
+                rad + stroke_width / 2.0,
                 0.005,
                 OUTLINE_COLOR,
             );
         }
     }
 
+    // The selected body's velocity and acceleration arrows. Their lengths are in world units so
+    // they zoom with the bodies, the line width and heads are a fraction of the screen so they stay
+    // readable at any zoom.
+    pub fn draw_motion_arrows(&self, builder: &mut MeshBuilder, ctx: &mut Context, entity: Entity) {
+        let positions = self.world.read_storage::<Position>();
+        let kinematics = self.world.read_storage::<Kinematics>();
+        let scale = self.world.fetch::<MotionArrows>().scale;
+
+        if let (Some(pos), Some(kine)) = (positions.get(entity), kinematics.get(entity)) {
+            let screen_width = graphics::screen_coordinates(ctx).w;
+            let line_width = screen_width * 0.003;
+            let head_len = screen_width * 0.015;
+
+            let vel = kine.vel * scale;
+            let accel = kine.accel * scale.powi(2);
+            [(vel, VELOCITY_ARROW_COLOR), (accel, ACCEL_ARROW_COLOR)]
+                .iter()
+                .for_each(|(arrow, color)| {
+                    draw_arrow(builder, pos.0, *arrow, line_width, head_len, *color)
+                });
+        }
+    }
+
     pub fn draw_gui(&mut self, ctx: &mut Context) {
         let hidpi_factor = self.hidpi_factor;
 
@@ -158,6 +1050,7 @@ impl<'a, 'b> MainState<'a, 'b> {
         let speed_graphs = self.world.read_storage::<SpeedGraph>();
         let xvel_graphs = self.world.read_storage::<XVelGraph>();
         let yvel_graphs = self.world.read_storage::<YVelGraph>();
+        let accel_mag_graphs = self.world.read_storage::<AccelMagGraph>();
 
         // adds graph data to gui
         macro_rules! register_graph_data {
@@ -171,6 +1064,7 @@ impl<'a, 'b> MainState<'a, 'b> {
         register_graph_data!(speed_graphs, SpeedGraph, GraphType::Speed);
         register_graph_data!(xvel_graphs, XVelGraph, GraphType::XVel);
         register_graph_data!(yvel_graphs, YVelGraph, GraphType::YVel);
+        register_graph_data!(accel_mag_graphs, AccelMagGraph, GraphType::AccelMag);
 
         if let Some(e) = self.selected_entity {
             if self.world.is_alive(e) {
@@ -181,15 +1075,51 @@ impl<'a, 'b> MainState<'a, 'b> {
                     let mut masses_mut = self.world.write_storage::<Mass>();
                     let mut radii_mut = self.world.write_storage::<Radius>();
                     let mut trails_mut = self.world.write_storage::<Trail>();
+                    let mut draws_mut = self.world.write_storage::<Draw>();
+                    let mut labels_mut = self.world.write_storage::<Label>();
+                    let mut anchors_mut = self.world.write_storage::<Anchored>();
+                    let mut kinematics_mut = self.world.write_storage::<Kinematics>();
 
                     masses_mut
                         .insert(e, Mass(self.imgui_wrapper.render_data.mass))
                         .unwrap_or(None);
-                    radii_mut
-                        .insert(e, Radius(self.imgui_wrapper.render_data.rad))
+                    let render_data = &self.imgui_wrapper.render_data;
+                    let rad = self
+                        .world
+                        .fetch::<Density>()
+                        .radius(render_data.mass)
+                        .unwrap_or(render_data.rad);
+                    radii_mut.insert(e, Radius(rad)).unwrap_or(None);
+                    draws_mut
+                        .insert(e, Draw(self.imgui_wrapper.render_data.color.into()))
                         .unwrap_or(None);
-                    trails_mut.get_mut(e).unwrap().max_len =
-                        self.imgui_wrapper.render_data.trail_len;
+                    let trail = trails_mut.get_mut(e).unwrap();
+                    trail.max_len = self.imgui_wrapper.render_data.trail_len;
+                    trail.enabled = self.imgui_wrapper.render_data.trail_enabled;
+                    trail.color = self.imgui_wrapper.render_data.trail_color.into();
+
+                    // a body that's just been anchored is stopped so it starts from rest if it's
+                    // released
+                    if self.imgui_wrapper.render_data.anchored {
+                        if !anchors_mut.contains(e) {
+                            if let Some(kine) = kinematics_mut.get_mut(e) {
+                                kine.vel = Vector::new(0.0, 0.0);
+                            }
+                        }
+                        anchors_mut.insert(e, Anchored).unwrap_or(None);
+                    } else {
+                        anchors_mut.remove(e);
+                    }
+
+                    // clearing the name removes the label
+                    let label = self.imgui_wrapper.render_data.label.to_str().trim();
+                    if label.is_empty() {
+                        labels_mut.remove(e);
+                    } else {
+                        labels_mut
+                            .insert(e, Label(label.to_owned()))
+                            .unwrap_or(None);
+                    }
                 }
 
                 self.world.entities().entity(e.id());
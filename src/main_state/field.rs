@@ -0,0 +1,105 @@
+use ggez::graphics::Rect;
+
+use crate::{Point, Vector};
+
+// A scalar field sampled on a regular grid of points covering a rectangle of world space.
+// Used by overlays that need to evaluate something everywhere on screen instead of per body.
+pub struct FieldGrid {
+    pub rect: Rect,
+    pub cols: usize,
+    pub rows: usize,
+    pub values: Vec<f32>,
+}
+
+impl FieldGrid {
+    pub fn sample(rect: Rect, cols: usize, rows: usize, field: impl Fn(Point) -> f32) -> Self {
+        let cols = cols.max(2);
+        let rows = rows.max(2);
+
+        let mut grid = FieldGrid {
+            rect,
+            cols,
+            rows,
+            values: Vec::with_capacity(cols * rows),
+        };
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let value = field(grid.point(col, row));
+                grid.values.push(value);
+            }
+        }
+
+        grid
+    }
+
+    pub fn cell_size(&self) -> Vector {
+        Vector::new(
+            self.rect.w / (self.cols - 1) as f32,
+            self.rect.h / (self.rows - 1) as f32,
+        )
+    }
+
+    pub fn point(&self, col: usize, row: usize) -> Point {
+        let cell_size = self.cell_size();
+        Point::new(
+            self.rect.x + col as f32 * cell_size.x,
+            self.rect.y + row as f32 * cell_size.y,
+        )
+    }
+
+    pub fn value(&self, col: usize, row: usize) -> f32 {
+        self.values[row * self.cols + col]
+    }
+
+    // marching squares, returns the line segments where the field crosses the threshold
+    pub fn contour(&self, threshold: f32) -> Vec<[Point; 2]> {
+        let mut segments = Vec::new();
+
+        for row in 0..self.rows - 1 {
+            for col in 0..self.cols - 1 {
+                // corners in clockwise order starting from the top left
+                let corners = [
+                    (col, row),
+                    (col + 1, row),
+                    (col + 1, row + 1),
+                    (col, row + 1),
+                ];
+                let values: Vec<f32> = corners.iter().map(|&(c, r)| self.value(c, r)).collect();
+
+                // where the threshold crosses each edge, linearly interpolated
+                let crossings: Vec<Point> = (0..4)
+                    .filter_map(|i| {
+                        let j = (i + 1) % 4;
+                        if (values[i] >= threshold) == (values[j] >= threshold) {
+                            return None;
+                        }
+
+                        let t = (threshold - values[i]) / (values[j] - values[i]);
+                        let p1 = self.point(corners[i].0, corners[i].1);
+                        let p2 = self.point(corners[j].0, corners[j].1);
+                        Some(p1 + (p2 - p1) * t)
+                    })
+                    .collect();
+
+                match crossings.len() {
+                    2 => segments.push([crossings[0], crossings[1]]),
+                    4 => {
+                        // saddle, use the center to decide which corners are connected
+                        let center = values.iter().sum::<f32>() / 4.0;
+                        if (center >= threshold) == (values[0] >= threshold) {
+                            segments.push([crossings[0], crossings[1]]);
+                            segments.push([crossings[2], crossings[3]]);
+                        } else {
+                            segments.push([crossings[0], crossings[3]]);
+                            segments.push([crossings[1], crossings[2]]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        segments
+    }
+}
@@ -1,3 +1,4 @@
 mod draw_utils;
+mod field;
 pub mod state;
 mod update_utils;
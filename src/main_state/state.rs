@@ -19,18 +19,33 @@ use crate::ecs::{
     },
     entities::{create_body, create_preview, new_body, new_preview},
     resources::{
-        FollowSelectedBody, MousePos, NewPreview, Paused, RelativeTrails, Resolution, StartPoint,
+        AimMode, Density, FollowSelectedBody, LaunchScale, Metaballs, MotionArrows, MousePos,
+        NewPreview, NudgeStep, PauseOnFocusLoss, Paused, PredictSelected, ReferenceFrame,
+        RelativeTrails, Resolution, SelectedEntity, ShowCenterOfMass, ShowFps, StartPoint,
     },
+    world::Snapshot,
 };
 
 use crate::{Point, Vector};
 
 use crate::gui::imgui_wrapper::*;
-use crate::main_state::update_utils::calc_offset;
+use crate::main_state::field::FieldGrid;
+use crate::main_state::update_utils::{calc_offset, Recording};
+use crate::presets::circular_orbit_velocity;
+use crate::screenshot::save_screenshot;
 
 #[allow(unused_imports)]
 use microprofile::scope;
 
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// how many created bodies Ctrl+Z can go back through
+const MAX_UNDO: usize = 50;
+
+// how many screen pixels outside a body a click can be and still pick it, at any zoom
+const SELECT_TOLERANCE_PIXELS: f32 = 12.5;
+
 pub fn scale_pos(point: impl Into<Point>, coords: graphics::Rect, resolution: Vector) -> Point {
     let mut np: Point = point.into();
     np.x *= coords.w / resolution.x;
@@ -47,8 +62,43 @@ pub struct MainState<'a, 'b> {
     pub imgui_wrapper: ImGuiWrapper,
     pub hidpi_factor: f32,
     pub selected_entity: Option<Entity>,
-    pub creating: bool,
+    pub tool: Tool,
+    pub measure_start: Option<Point>,
+    // the body the measurement started on, that end follows it
+    pub measure_start_body: Option<Entity>,
+    pub moving_entity: Option<Entity>,
     pub items_hovered: bool,
+    // set when the sim was paused by losing focus, so only that pause is undone on regaining it
+    pub paused_by_focus_loss: bool,
+    pub steps_since_checkpoint: usize,
+    pub last_checkpoint: Instant,
+    pub next_checkpoint: usize,
+    pub steps_since_stats_log: usize,
+    // rows written to the stats log, 0 means the file hasn't been started yet
+    pub stats_log_rows: usize,
+    // simulated time and steps since the start, for the stats log
+    pub sim_time: f32,
+    pub sim_steps: usize,
+    // a copy of the world with one body nudged, stepped alongside it to show chaotic divergence
+    pub perturbed_world: Option<World>,
+    // zoom left to apply with ZoomSmoothing, and the point in the window it's about
+    pub zoom_velocity: f32,
+    pub zoom_focus: Point,
+    pub scroll_accel: f32,
+    pub last_scroll: Instant,
+    pub recording: Option<Recording>,
+    // the last samples of the potential map overlay, reused for a few frames
+    pub potential_map: Option<FieldGrid>,
+    // bodies made with the create tool, newest last, for Ctrl+Z
+    pub created_bodies: VecDeque<Entity>,
+    // the state before each of the last RewindDepth steps, newest last
+    pub rewind_buffer: VecDeque<Snapshot>,
+    // F12 was pressed, the screenshot is taken partway through the next draw
+    pub screenshot_requested: bool,
+    // where in the preview's trail it first hit a body, the rest of the trail is drawn red
+    pub preview_collision: Option<usize>,
+    // preview steps taken since it hit a body, it's made again after PREVIEW_STEPS_AFTER_COLLISION
+    pub steps_since_collision: usize,
 }
 
 impl<'a, 'b> MainState<'a, 'b> {
@@ -66,8 +116,105 @@ impl<'a, 'b> MainState<'a, 'b> {
             imgui_wrapper,
             hidpi_factor,
             selected_entity: None,
-            creating: false,
+            tool: Tool::Select,
+            measure_start: None,
+            measure_start_body: None,
+            moving_entity: None,
             items_hovered: false,
+            paused_by_focus_loss: false,
+            steps_since_checkpoint: 0,
+            last_checkpoint: Instant::now(),
+            next_checkpoint: 0,
+            steps_since_stats_log: 0,
+            stats_log_rows: 0,
+            sim_time: 0.0,
+            sim_steps: 0,
+            perturbed_world: None,
+            zoom_velocity: 0.0,
+            zoom_focus: Point::new(0.0, 0.0),
+            scroll_accel: 1.0,
+            last_scroll: Instant::now(),
+            recording: None,
+            potential_map: None,
+            created_bodies: VecDeque::with_capacity(MAX_UNDO),
+            rewind_buffer: VecDeque::new(),
+            screenshot_requested: false,
+            preview_collision: None,
+            steps_since_collision: 0,
+        }
+    }
+
+    // The radius that a newly created body will have. The creation preview uses this too so that
+    // it always matches the body that gets created.
+    pub fn new_body_radius(&self) -> f32 {
+        let render_data = &self.imgui_wrapper.render_data;
+        self.world
+            .fetch::<Density>()
+            .radius(render_data.create_mass)
+            .unwrap_or(render_data.create_rad)
+    }
+
+    // the velocity of a body launched by dragging from start to end
+    pub fn launch_velocity(&self, start: Point, end: Point) -> Vector {
+        let vel = (start - end) * self.world.fetch::<LaunchScale>().0;
+        match *self.world.fetch::<AimMode>() {
+            AimMode::Slingshot => vel,
+            AimMode::Direct => -vel,
+        }
+    }
+
+    // The body under the cursor, with some leeway so small bodies can still be clicked. If several
+    // are in reach the one whose edge is closest wins, bodies the cursor is inside count as 0 away
+    // and are then ordered by how close their centers are.
+    pub fn body_at(&self, ctx: &Context, x: f32, y: f32) -> Option<Entity> {
+        let resolution = self.world.fetch::<Resolution>().0;
+        let positions = self.world.read_storage::<Position>();
+        let radii = self.world.read_storage::<Radius>();
+        let entities = self.world.entities();
+
+        let coords = ggez::graphics::screen_coordinates(ctx);
+        let mouse_pos = scale_pos([x, y], coords, resolution);
+        let tolerance = SELECT_TOLERANCE_PIXELS * (coords.w / resolution.x);
+
+        (&entities, &positions, &radii)
+            .join()
+            .map(|(e, pos, rad)| {
+                let dist = pos.dist(mouse_pos);
+                (e, (dist - rad.0).max(0.0), dist)
+            })
+            .filter(|(_, edge_dist, _)| *edge_dist <= tolerance)
+            .min_by(|(_, edge1, dist1), (_, edge2, dist2)| {
+                (edge1, dist1)
+                    .partial_cmp(&(edge2, dist2))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(e, _, _)| e)
+    }
+
+    pub fn select_at(&mut self, ctx: &Context, x: f32, y: f32) {
+        let entity = self.body_at(ctx, x, y);
+        self.select(entity);
+    }
+
+    // selects entity and opens its side menu, or just closes the menu for None
+    pub fn select(&mut self, entity: Option<Entity>) {
+        self.imgui_wrapper.remove_sidemenu();
+
+        if self.world.fetch::<RelativeTrails>().enabled {
+            (&mut self.world.write_storage::<Trail>())
+                .join()
+                .for_each(|trail| {
+                    trail.points.clear();
+                });
+        }
+
+        self.world.insert(FollowSelectedBody(false));
+        self.selected_entity = entity;
+        self.imgui_wrapper.render_data.entity_selected = self.selected_entity.is_some();
+        if self.selected_entity.is_some() {
+            self.imgui_wrapper
+                .shown_menus
+                .insert(UiChoice::SideMenu(self.selected_entity));
         }
     }
 }
@@ -76,7 +223,7 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
         microprofile::flip();
         microprofile::scope!("Update", "update");
-        self.process_gui_signals();
+        self.process_gui_signals(ctx);
 
         // unselect selected entity if it collided
         if let Some(e) = self.selected_entity {
@@ -90,6 +237,15 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
                 self.world.fetch_mut::<RelativeTrails>().pos = Some(selected_pos.0);
             }
         }
+        self.world.insert(SelectedEntity(self.selected_entity));
+
+        // go back to graphing absolute velocities if the reference body collided
+        let reference = self.world.fetch::<ReferenceFrame>().0;
+        if let Some(e) = reference {
+            if !self.world.is_alive(e) {
+                self.world.insert(ReferenceFrame(None));
+            }
+        }
 
         // apply camera movement
         let offset = calc_offset(ctx);
@@ -104,10 +260,7 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
             ggez::graphics::set_screen_coordinates(ctx, screen_coordinates).unwrap_or(());
         }
 
-        // if ggez::timer::ticks(ctx) % 120 == 0 {
-        //     dbg!(ggez::timer::fps(ctx));
-        // }
-
+        self.update_zoom(ctx);
         self.run_physics_systems(ctx);
 
         Ok(())
@@ -123,6 +276,8 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
         self.update_gui_data();
 
         let mut builder = graphics::MeshBuilder::new();
+        self.draw_potential_map(&mut builder, ctx);
+        self.draw_grid(&mut builder, ctx);
 
         if self.world.fetch::<RelativeTrails>().enabled {
             let mut trail_builder = graphics::MeshBuilder::new();
@@ -137,18 +292,48 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
             self.draw_trails(&mut builder);
         }
 
-        self.draw_bodies(&mut builder);
+        self.draw_boundary(&mut builder, ctx);
+        if self.world.fetch::<ShowCenterOfMass>().0 {
+            self.draw_center_of_mass(&mut builder, ctx);
+        }
+        self.draw_bodies(&mut builder, ctx);
+        if self.world.fetch::<Metaballs>().0 {
+            self.draw_metaballs(&mut builder, ctx);
+        }
+        self.draw_perturbed(&mut builder);
         self.draw_preview(&mut builder, ctx);
+        self.draw_measurement(&mut builder, ctx);
         self.draw_vector_graphs(&mut builder, ctx);
 
         if let Some(ent) = self.selected_entity {
+            if self.world.fetch::<PredictSelected>().0 {
+                self.draw_prediction(&mut builder, ctx, ent);
+            }
             self.draw_selected_outline(&mut builder, ent);
+            if self.world.fetch::<MotionArrows>().enabled {
+                self.draw_motion_arrows(&mut builder, ctx, ent);
+            }
         }
 
         if let Ok(mesh) = builder.build(ctx) {
             graphics::draw(ctx, &mesh, graphics::DrawParam::new()).expect("error drawing mesh");
         }
 
+        self.draw_labels(ctx);
+        self.draw_measurement_readout(ctx);
+
+        if self.world.fetch::<ShowFps>().0 {
+            self.draw_fps(ctx);
+        }
+
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            self.imgui_wrapper.render_data.status = match save_screenshot(ctx) {
+                Ok(path) => format!("Saved {}", path.display()),
+                Err(e) => format!("Error taking screenshot: {}", e),
+            };
+        }
+
         self.draw_gui(ctx);
         self.update_sim_data();
 
@@ -170,56 +355,35 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
 
         if !self.items_hovered {
             match button {
-                MouseButton::Right => {
-                    self.imgui_wrapper.remove_sidemenu();
-
-                    if self.world.fetch::<RelativeTrails>().enabled {
-                        (&mut self.world.write_storage::<Trail>())
-                            .join()
-                            .for_each(|trail| {
-                                trail.points.clear();
-                            });
-                    }
-
+                // shift right click sets the reference frame, clicking empty space clears it
+                MouseButton::Right if input::keyboard::is_mod_active(ctx, KeyMods::SHIFT) => {
+                    let reference = self.body_at(ctx, x, y);
+                    self.world.insert(ReferenceFrame(reference));
+                }
+                // ctrl right drag measures without changing the tool
+                MouseButton::Right if input::keyboard::is_mod_active(ctx, KeyMods::CTRL) => {
+                    self.start_measurement(ctx, x, y);
+                }
+                MouseButton::Right => self.select_at(ctx, x, y),
+                MouseButton::Left => {
+                    let p = Point::new(x, y);
+                    let coords = ggez::graphics::screen_coordinates(ctx);
                     let resolution = self.world.fetch::<Resolution>().0;
-                    let screen_coordinates = graphics::screen_coordinates(ctx);
-                    self.selected_entity = None;
-                    self.imgui_wrapper.remove_sidemenu();
-                    self.world.insert(FollowSelectedBody(false));
-                    self.imgui_wrapper.render_data.entity_selected = false;
 
-                    let positions = self.world.read_storage::<Position>();
-                    let radii = self.world.read_storage::<Radius>();
-                    let entities = self.world.entities();
+                    match self.tool {
+                        Tool::Select => self.select_at(ctx, x, y),
+                        // set up for creating new body
+                        Tool::Create => {
+                            self.world
+                                .insert(StartPoint(Some(scale_pos(p, coords, resolution))));
 
-                    let coords = ggez::graphics::screen_coordinates(ctx);
-                    let mouse_pos = scale_pos([x, y], coords, resolution);
-
-                    for (e, pos, rad) in (&entities, &positions, &radii).join() {
-                        let mselect_rad = 12.5 * (screen_coordinates.w / resolution.x);
-                        if pos.dist(mouse_pos) <= rad.0 + mselect_rad {
-                            self.selected_entity = Some(e);
-                            self.imgui_wrapper.render_data.entity_selected = true;
-                            self.imgui_wrapper
-                                .shown_menus
-                                .insert(UiChoice::SideMenu(self.selected_entity));
-                            break;
+                            let rad = self.new_body_radius();
+                            create_preview(&mut self.world, new_preview(p, [0.0, 0.0], rad));
+                        }
+                        Tool::Measure => self.start_measurement(ctx, x, y),
+                        Tool::Move => {
+                            self.moving_entity = self.body_at(ctx, x, y);
                         }
-                    }
-                }
-                MouseButton::Left => {
-                    // set up for creating new body
-                    if self.creating {
-                        let p = Point::new(x, y);
-                        let coords = ggez::graphics::screen_coordinates(ctx);
-                        let resolution = self.world.fetch::<Resolution>().0;
-                        self.world
-                            .insert(StartPoint(Some(scale_pos(p, coords, resolution))));
-
-                        create_preview(
-                            &mut self.world,
-                            new_preview(p, [0.0, 0.0], self.imgui_wrapper.render_data.create_rad),
-                        );
                     }
                 }
                 _ => {}
@@ -238,30 +402,58 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
         let start_point = self.world.fetch::<StartPoint>().0;
         let resolution = self.world.fetch::<Resolution>().0;
 
+        if button == MouseButton::Left || button == MouseButton::Right {
+            if let Some(readout) = self.measurement_readout(ctx) {
+                self.imgui_wrapper.render_data.status = readout.replace('\n', ", ");
+            }
+            self.measure_start = None;
+            self.measure_start_body = None;
+        }
+        if button == MouseButton::Left {
+            self.moving_entity = None;
+        }
+
         if let Some(start_point) = start_point {
             match button {
                 // create new body
                 MouseButton::Left => {
-                    if self.creating && !self.imgui_wrapper.sent_signals.contains(&UiSignal::Create)
+                    if self.tool == Tool::Create
+                        && !self
+                            .imgui_wrapper
+                            .sent_signals
+                            .contains(&UiSignal::SetTool(Tool::Create))
                     {
                         let mut p = Point::new(x, y);
                         let coords = ggez::graphics::screen_coordinates(ctx);
                         p = scale_pos(p, coords, resolution);
 
-                        self.selected_entity = Some(create_body(
-                            &mut self.world,
-                            new_body(
-                                start_point,
-                                (start_point - p) * 0.025,
-                                self.imgui_wrapper.render_data.create_mass,
-                                self.imgui_wrapper.render_data.create_rad,
-                            ),
-                        ));
+                        let rad = self.new_body_radius();
+                        let mut vel = self.launch_velocity(start_point, p);
+                        // the drag only picks which way around the orbit goes
+                        if self.imgui_wrapper.render_data.create_circular_orbit {
+                            if let Some(orbit_vel) =
+                                circular_orbit_velocity(&self.world, start_point, vel)
+                            {
+                                vel = orbit_vel;
+                            }
+                        }
+                        let mut body = new_body(
+                            start_point,
+                            vel,
+                            self.imgui_wrapper.render_data.create_mass,
+                            rad,
+                        );
+                        body.3 = Draw(self.imgui_wrapper.render_data.create_color.into());
+                        let entity = create_body(&mut self.world, body);
+                        self.selected_entity = Some(entity);
+                        self.created_bodies.push_back(entity);
+                        if self.created_bodies.len() > MAX_UNDO {
+                            self.created_bodies.pop_front();
+                        }
                         self.world.insert(StartPoint(None));
                     }
                 }
-                // _ => dbg!(),
-                _ => {},
+                _ => {}
             }
         }
 
@@ -274,23 +466,30 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
             .insert(MousePos(input::mouse::position(ctx).into()));
         let mut coords = ggez::graphics::screen_coordinates(ctx);
 
-        if (dx * dx + dy * dy) > 0.1 { // this is needed for windows since mouse_motion_event runs every frame
+        if (dx * dx + dy * dy) > 0.1 {
+            // this is needed for windows since mouse_motion_event runs every frame
             self.delete_preview();
 
-
             let start_point = self.world.fetch::<StartPoint>().0;
             if let Some(sp) = start_point {
                 let resolution = self.world.fetch::<Resolution>().0;
                 let p = scale_pos([x, y], coords, resolution);
+                let rad = self.new_body_radius();
+                let vel = self.launch_velocity(sp, p);
 
-                create_preview(
-                    &mut self.world,
-                    new_preview(
-                        sp,
-                        (sp - p) * 0.025,
-                        self.imgui_wrapper.render_data.create_rad,
-                        ),
-                        );
+                create_preview(&mut self.world, new_preview(sp, vel, rad));
+            }
+        }
+
+        if let Some(e) = self.moving_entity {
+            let resolution = self.world.fetch::<Resolution>().0;
+            let p = scale_pos([x, y], coords, resolution);
+            if let Some(pos) = self.world.write_storage::<Position>().get_mut(e) {
+                pos.0 = p;
+            }
+            // a dragged body is dropped at rest instead of keeping its old momentum
+            if let Some(kine) = self.world.write_storage::<Kinematics>().get_mut(e) {
+                kine.vel = Vector::new(0.0, 0.0);
             }
         }
 
@@ -307,48 +506,77 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
     }
 
     fn mouse_wheel_event(&mut self, ctx: &mut Context, _x: f32, y: f32) {
-        // zoom
-        let mouse_pos = input::mouse::position(ctx);
-        let mut offset = graphics::screen_coordinates(ctx);
-
-        let prev_zoom = offset.w / crate::SCREEN_X;
-        let zoom = prev_zoom * (1.0 - (y * 0.05));
-
-        let resolution = self.world.fetch::<Resolution>().0;
-
-        let focus: Vector = Vector::new(mouse_pos.x, mouse_pos.y);
-
-        let mut scaled_focus1: Vector = focus;
-        scaled_focus1.x *= offset.w / resolution.x;
-        scaled_focus1.y *= offset.h / resolution.y;
-
-        offset.w = zoom * crate::SCREEN_X;
-        offset.h = zoom * crate::SCREEN_Y / (resolution.x / resolution.y);
-
-        let mut scaled_focus2: Vector = focus;
-        scaled_focus2.x *= offset.w / resolution.x;
-        scaled_focus2.y *= offset.h / resolution.y;
-
-        let delta_focus = scaled_focus2 - scaled_focus1;
-
-        offset.x -= delta_focus.x;
-        offset.y -= delta_focus.y;
-
-        graphics::set_screen_coordinates(ctx, offset).unwrap_or(());
+        self.scroll_zoom(ctx, y);
     }
 
     fn key_down_event(
         &mut self,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         keycode: KeyCode,
         keymods: KeyMods,
-        _repeat: bool,
-        ) {
+        repeat: bool,
+    ) {
+        // while typing in a text box the keys only go to imgui
+        if self.imgui_wrapper.imgui.io().want_capture_keyboard {
+            self.imgui_wrapper.update_key_down(keycode, keymods);
+            return;
+        }
+
         match keycode {
+            KeyCode::S if keymods.contains(KeyMods::CTRL) => {
+                self.imgui_wrapper.sent_signals.push(UiSignal::SaveState)
+            }
+            KeyCode::E if keymods.contains(KeyMods::CTRL) => self.export_recording(),
+            KeyCode::D if keymods.contains(KeyMods::CTRL) => self.duplicate_selected(),
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+                if keymods.contains(KeyMods::CTRL) =>
+            {
+                let step = self.world.fetch::<NudgeStep>().0;
+                let delta = match keycode {
+                    KeyCode::Up => Vector::new(0.0, -step),
+                    KeyCode::Down => Vector::new(0.0, step),
+                    KeyCode::Left => Vector::new(-step, 0.0),
+                    _ => Vector::new(step, 0.0),
+                };
+                self.nudge_selected(delta);
+            }
+            // reopens whatever Ctrl+S last wrote
+            KeyCode::O if keymods.contains(KeyMods::CTRL) => {
+                let render_data = &mut self.imgui_wrapper.render_data;
+                render_data.load_filename = render_data.save_filename.clone();
+                self.imgui_wrapper.sent_signals.push(UiSignal::LoadState);
+            }
             KeyCode::Space => self.world.get_mut::<Paused>().unwrap().toggle(),
+            // holding the key down only steps once
+            KeyCode::Period if !repeat && self.world.fetch::<Paused>().0 => self.step(),
             KeyCode::Escape => self.imgui_wrapper.remove_sidemenu(),
-            KeyCode::F => self.world.insert(FollowSelectedBody(true)),
+            KeyCode::Home => self.reset_camera(ctx),
+            KeyCode::F11 if !repeat => self.toggle_fullscreen(ctx),
+            KeyCode::F12 if !repeat => self.screenshot_requested = true,
+            KeyCode::F3 if !repeat => self
+                .imgui_wrapper
+                .sent_signals
+                .push(UiSignal::ToggleTimings),
+            KeyCode::Z if keymods.contains(KeyMods::CTRL) => self.undo_create(),
+            KeyCode::Z => self.zoom_to_fit(ctx),
+            KeyCode::O => self.pan_to(ctx, Point::new(0.0, 0.0)),
+            KeyCode::C => self.pan_to_center_of_mass(ctx),
+            KeyCode::P => self.world.get_mut::<ShowFps>().unwrap().toggle(),
+            KeyCode::F => self.world.get_mut::<FollowSelectedBody>().unwrap().toggle(),
             KeyCode::R => self.imgui_wrapper.sent_signals.push(UiSignal::Delete),
+            KeyCode::Delete | KeyCode::Back => {
+                self.imgui_wrapper.sent_signals.push(UiSignal::Delete)
+            }
+            // slow down, speed up and reset how much time passes each step
+            KeyCode::LBracket | KeyCode::RBracket | KeyCode::Backslash => {
+                let dt = self.imgui_wrapper.render_data.dt;
+                self.set_dt(match keycode {
+                    KeyCode::LBracket => dt * 0.5,
+                    KeyCode::RBracket => dt * 2.0,
+                    _ => 1.0,
+                })
+            }
+            KeyCode::Tab => self.cycle_selection(!keymods.contains(KeyMods::SHIFT)),
             KeyCode::T => self.imgui_wrapper.sent_signals.push(UiSignal::ToggleTrails),
             KeyCode::G => self
                 .imgui_wrapper
@@ -368,9 +596,9 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
                 0.,
                 crate::SCREEN_X,
                 crate::SCREEN_Y * aspect_ratio as f32,
-                ),
-                )
-            .expect("error resizing");
+            ),
+        )
+        .expect("error resizing");
         let resolution = Vector::new(width, height);
         self.imgui_wrapper.resolution = resolution;
         self.world.insert(Resolution(resolution));
@@ -383,4 +611,16 @@ impl<'a, 'b> EventHandler for MainState<'a, 'b> {
     fn text_input_event(&mut self, _ctx: &mut Context, val: char) {
         self.imgui_wrapper.update_text(val);
     }
+
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) {
+        if gained {
+            if self.paused_by_focus_loss {
+                self.world.insert(Paused(false));
+                self.paused_by_focus_loss = false;
+            }
+        } else if self.world.fetch::<PauseOnFocusLoss>().0 && !self.world.fetch::<Paused>().0 {
+            self.world.insert(Paused(true));
+            self.paused_by_focus_loss = true;
+        }
+    }
 }
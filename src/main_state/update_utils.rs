@@ -1,38 +1,103 @@
 use crate::ecs::{
-    components::{AccelGraph, Preview, SpeedGraph, Trail, XVelGraph, YVelGraph},
+    components::{
+        AccelGraph, AccelMagGraph, Draw, Kinematics, Mass, Position, Preview, Radius, SpeedGraph,
+        Trail, XVelGraph, YVelGraph,
+    },
     resources::{
-        EnableTrails, FollowSelectedBody, MainIterations, MousePos, NewPreview, Paused,
-        PreviewIterations, RelativeTrails, Resolution, StartPoint,
+        AutoSave, CollisionLog, ColorDisplayMode, DiagnosticsLog, EnableTrails, FollowSelectedBody,
+        Fullscreen, IsolateSelected, MainIterations, MergeThreshold, Metaballs, MousePos,
+        NewPreview, Paused, PhysicsTimings, PredictSelected, PreviewIterations, RadiusDisplayMode,
+        ReferenceFrame, RelativeTrails, Resolution, RewindDepth, ShowCenterOfMass, StartPoint,
+        StatsLog, SystemStats, TimeMode, TrailScope, TrailStyle, TrailTaper, ZoomSmoothing, DT,
     },
     systems::graph_sys::GraphType,
+    world::{clone_bodies, Snapshot},
 };
 
-use crate::ecs::entities::{create_preview, new_preview};
+use crate::ecs::entities::{body_id, create_body, create_preview, new_body, new_preview};
 use crate::gui::imgui_wrapper::{UiChoice, UiSignal};
 use crate::main_state::state::scale_pos;
 use crate::main_state::state::MainState;
+use crate::physics::{
+    apply_linear_kick, apply_spin, bounds_of, center_of_mass, copy_physics_settings, do_physics,
+    update_stats,
+};
+use crate::presets::spawn_preset;
 use crate::saveload::{load_world, save_world};
-use crate::Vector;
+use crate::{Point, Vector};
 
 use specs::prelude::*;
 
-use ggez::{input, input::keyboard::KeyCode, Context};
+use ggez::{
+    conf::FullscreenType,
+    event::EventHandler,
+    graphics, input,
+    input::keyboard::{KeyCode, KeyMods},
+    Context,
+};
 
 use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::time::{Duration, Instant};
 
 const CAMERA_SPEED: f32 = 1.5;
 
+// DT is the step for a frame at this framerate in TimeMode::RealTime
+const REAL_TIME_FPS: f32 = 60.0;
+// the most a real time step can be stretched, so a lag spike can't make the next frame even slower
+const MAX_REAL_TIME_SCALE: f32 = 3.0;
+
+// how far [ and ] can slow down or speed up time, as a multiple of a DT of 1
+const MIN_DT: f32 = 1.0 / 256.0;
+const MAX_DT: f32 = 64.0;
+
+// steps a preview keeps going after it hits a body before it starts over
+const PREVIEW_STEPS_AFTER_COLLISION: usize = 100;
+
+// fraction of the zoom per notch of the scroll wheel
+const ZOOM_STEP: f32 = 0.05;
+// with smoothing, the fraction of the remaining zoom that's applied each frame
+const ZOOM_EASE: f32 = 0.35;
+// zoom is the width of the view over SCREEN_X, so MIN_ZOOM is the furthest in
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 50.0;
+// how much wider the view is than the bodies when zooming to fit
+const FIT_MARGIN: f32 = 1.2;
+
+const STATS_LOG_PATH: &str = "stats.csv";
+const STATS_LOG_HEADER: &str = concat!(
+    "step,time,kinetic,potential,total,dissipated,",
+    "momentum_x,momentum_y,angular_momentum,com_x,com_y\n"
+);
+
+const TRAJECTORY_HEADER: &str = "step,x,y,vel_x,vel_y\n";
+
+// The path of the selected body, recorded every step until it's toggled off or another body is
+// selected. Each recording is written to its own file.
+pub struct Recording {
+    pub entity: Entity,
+    pub path: String,
+    pub rows: Vec<(usize, Point, Vector)>,
+}
+
 impl<'a, 'b> MainState<'a, 'b> {
     pub fn run_physics_systems(&mut self, ctx: &mut ggez::Context) {
         let preview_iterations = self.world.fetch::<PreviewIterations>().0;
-        if !self.world.fetch::<Paused>().0 {
-            let main_iterations = self.world.fetch::<MainIterations>().0;
-
-            // do_physics(&mut self.world, ctx);
-            (0..main_iterations).for_each(|_| {
-                self.main_dispatcher.dispatch_par(&self.world);
-                self.world.maintain();
-            });
+        // holding comma rewinds, unless it's being typed into a text box
+        let rewinding = input::keyboard::is_key_pressed(ctx, KeyCode::Comma)
+            && !self.imgui_wrapper.imgui.io().want_capture_keyboard;
+        if rewinding {
+            self.rewind();
+        } else if !self.world.fetch::<Paused>().0 {
+            let dt = self.world.fetch::<DT>().0;
+            let time_mode = *self.world.fetch::<TimeMode>();
+            if time_mode == TimeMode::RealTime {
+                self.world
+                    .insert(DT(real_time_dt(dt, ggez::timer::delta(ctx))));
+            }
+            self.step();
+            self.world.insert(DT(dt));
         }
         if let Some(e) = self.selected_entity {
             if !self.world.is_alive(e) {
@@ -42,8 +107,27 @@ impl<'a, 'b> MainState<'a, 'b> {
 
         (0..preview_iterations).for_each(|_| {
             self.preview_dispatcher.dispatch(&self.world);
-            // if preview collided, delete it and make a new one
+            // the preview keeps going through the first body it hits so the rest of its path can be
+            // drawn as a warning, then it's deleted and made again
+            let trail_len = {
+                let trails = self.world.read_storage::<Trail>();
+                let previews = self.world.read_storage::<Preview>();
+                (&trails, &previews)
+                    .join()
+                    .map(|(trail, _)| trail.points.len())
+                    .next()
+                    .unwrap_or(0)
+            };
             if self.world.fetch::<NewPreview>().0 {
+                if self.preview_collision.is_none() {
+                    self.preview_collision = Some(trail_len.saturating_sub(1));
+                }
+                self.world.insert(NewPreview(false));
+            }
+            if self.preview_collision.is_some() {
+                self.steps_since_collision += 1;
+            }
+            if self.steps_since_collision > PREVIEW_STEPS_AFTER_COLLISION {
                 self.delete_preview();
 
                 let coords = ggez::graphics::screen_coordinates(ctx);
@@ -53,36 +137,509 @@ impl<'a, 'b> MainState<'a, 'b> {
                     let resolution = self.world.fetch::<Resolution>().0;
                     let mouse_pos = self.world.fetch::<MousePos>().0;
                     let p = scale_pos([mouse_pos.x, mouse_pos.y], coords, resolution);
+                    let rad = self.new_body_radius();
 
-                    create_preview(
-                        &mut self.world,
-                        new_preview(
-                            sp,
-                            (sp - p) * 0.025,
-                            self.imgui_wrapper.render_data.create_rad,
-                        ),
-                    );
+                    let vel = self.launch_velocity(sp, p);
+                    create_preview(&mut self.world, new_preview(sp, vel, rad));
                 }
+            }
+        });
+    }
 
-                self.world.insert(NewPreview(false));
+    // Advances the simulation by MainIterations steps of DT, this is one frame of running and what
+    // the step key does while paused.
+    pub fn step(&mut self) {
+        let main_iterations = self.world.fetch::<MainIterations>().0;
+        self.sync_perturbed_world();
+        self.world.fetch_mut::<PhysicsTimings>().reset();
+
+        (0..main_iterations).for_each(|i| {
+            self.push_snapshot();
+            do_physics(&mut self.world);
+            if let Some(perturbed_world) = &mut self.perturbed_world {
+                do_physics(perturbed_world);
             }
+            self.main_dispatcher.dispatch_par(&self.world);
+            self.world.maintain();
+            self.record_step(self.sim_steps + i + 1);
         });
+
+        self.sim_steps += main_iterations;
+        self.sim_time += main_iterations as f32 * self.world.fetch::<DT>().0;
+
+        self.update_checkpoints(main_iterations);
+        self.update_stats_log(main_iterations);
+    }
+
+    // keeps the state from before a step for rewinding, the oldest past RewindDepth are dropped
+    fn push_snapshot(&mut self) {
+        let depth = self.world.fetch::<RewindDepth>().0;
+        if depth > 0 {
+            self.rewind_buffer.push_back(Snapshot::capture(&self.world));
+        }
+        while self.rewind_buffer.len() > depth {
+            self.rewind_buffer.pop_front();
+        }
+    }
+
+    // Goes back MainIterations steps, or as far as the snapshots go. The snapshots that are passed
+    // are dropped, so unpausing carries on from the restored one.
+    pub fn rewind(&mut self) {
+        let main_iterations = self.world.fetch::<MainIterations>().0;
+        let steps = main_iterations.min(self.rewind_buffer.len());
+        let snapshot = match self
+            .rewind_buffer
+            .drain(self.rewind_buffer.len() - steps..)
+            .next()
+        {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        let remade = snapshot.restore(&mut self.world);
+        self.rewind_buffer
+            .iter_mut()
+            .for_each(|snapshot| snapshot.remap(&remade));
+
+        // anything pointing at a body that was made again follows it to its new entity
+        if let Some(&e) = self.selected_entity.and_then(|e| remade.get(&e)) {
+            let follow = self.world.fetch::<FollowSelectedBody>().0;
+            self.select(Some(e));
+            self.world.insert(FollowSelectedBody(follow));
+        }
+        self.moving_entity = self.moving_entity.map(|e| *remade.get(&e).unwrap_or(&e));
+        if let Some(recording) = &mut self.recording {
+            recording.entity = *remade.get(&recording.entity).unwrap_or(&recording.entity);
+        }
+        let reference = self.world.fetch::<ReferenceFrame>().0;
+        if let Some(&e) = reference.and_then(|e| remade.get(&e)) {
+            self.world.insert(ReferenceFrame(Some(e)));
+        }
+
+        // trails get a point a step, so they go back with the bodies
+        (&mut self.world.write_storage::<Trail>())
+            .join()
+            .for_each(|trail| {
+                let len = trail.points.len().saturating_sub(steps);
+                trail.points.truncate(len);
+            });
+
+        self.sim_steps = self.sim_steps.saturating_sub(steps);
+        self.sim_time = (self.sim_time - steps as f32 * self.world.fetch::<DT>().0).max(0.0);
+    }
+
+    // Replaces the perturbed world with a copy of the current one in which the selected body, or
+    // any body if none is selected, is nudged to the right by epsilon.
+    pub fn perturb(&mut self, epsilon: f32) {
+        let (perturbed_world, copies) = clone_bodies(&self.world);
+
+        let nudged = match self.selected_entity {
+            Some(e) => copies.get(&e).copied(),
+            None => copies.values().next().copied(),
+        };
+        if let Some(e) = nudged {
+            if let Some(pos) = perturbed_world.write_storage::<Position>().get_mut(e) {
+                pos.0.x += epsilon;
+            }
+        }
+
+        self.perturbed_world = Some(perturbed_world);
     }
 
-    pub fn process_gui_signals(&mut self) {
+    // the perturbed world only differs from the main one by the nudge, so it follows its settings
+    fn sync_perturbed_world(&mut self) {
+        if let Some(perturbed_world) = &mut self.perturbed_world {
+            copy_physics_settings(&self.world, perturbed_world);
+            perturbed_world.insert(*self.world.fetch::<EnableTrails>());
+            perturbed_world.insert(*self.world.fetch::<TrailScope>());
+        }
+    }
+
+    // saves a checkpoint if enough steps or time have passed since the last one
+    pub fn update_checkpoints(&mut self, steps: usize) {
+        let auto_save = *self.world.fetch::<AutoSave>();
+        if !auto_save.enabled {
+            return;
+        }
+
+        self.steps_since_checkpoint += steps;
+        let steps_due =
+            auto_save.interval_steps > 0 && self.steps_since_checkpoint >= auto_save.interval_steps;
+        let time_due = auto_save.interval_mins > 0.0
+            && self.last_checkpoint.elapsed().as_secs_f32() >= auto_save.interval_mins * 60.0;
+
+        if steps_due || time_due {
+            // the oldest checkpoint gets overwritten once there are auto_save.keep of them
+            let filename = format!("checkpoint_{}.ron", self.next_checkpoint);
+            self.imgui_wrapper.render_data.status =
+                match save_world(&self.world, format!("saved_systems/{}", filename)) {
+                    Ok(()) => format!("Saved {}", filename),
+                    Err(e) => format!("Error saving {}: {}", filename, e),
+                };
+
+            self.next_checkpoint = (self.next_checkpoint + 1) % auto_save.keep.max(1);
+            self.steps_since_checkpoint = 0;
+            self.last_checkpoint = Instant::now();
+        }
+    }
+
+    // appends a row to the stats log every interval, the file is started over when it's enabled
+    pub fn update_stats_log(&mut self, steps: usize) {
+        let stats_log = *self.world.fetch::<StatsLog>();
+        if !stats_log.enabled {
+            self.stats_log_rows = 0;
+            self.steps_since_stats_log = 0;
+            return;
+        }
+
+        self.steps_since_stats_log += steps;
+        if self.steps_since_stats_log < stats_log.interval_steps
+            || self.stats_log_rows >= stats_log.max_rows
+        {
+            return;
+        }
+        self.steps_since_stats_log = 0;
+
+        update_stats(&mut self.world);
+        let stats = *self.world.fetch::<SystemStats>();
+        let row = format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            self.sim_steps,
+            self.sim_time,
+            stats.kinetic,
+            stats.potential,
+            stats.total(),
+            stats.dissipated,
+            stats.momentum.x,
+            stats.momentum.y,
+            stats.angular_momentum,
+            stats.center_of_mass.x,
+            stats.center_of_mass.y,
+        );
+
+        let result = if self.stats_log_rows == 0 {
+            fs::write(STATS_LOG_PATH, format!("{}{}", STATS_LOG_HEADER, row))
+        } else {
+            OpenOptions::new()
+                .append(true)
+                .open(STATS_LOG_PATH)
+                .and_then(|mut file| file.write_all(row.as_bytes()))
+        };
+
+        match result {
+            Ok(()) => {
+                self.stats_log_rows += 1;
+                if self.stats_log_rows >= stats_log.max_rows {
+                    self.imgui_wrapper.render_data.status = format!("{} is full", STATS_LOG_PATH);
+                }
+            }
+            Err(e) => {
+                self.imgui_wrapper.render_data.status =
+                    format!("Error writing {}: {}", STATS_LOG_PATH, e);
+            }
+        }
+    }
+
+    pub fn toggle_recording(&mut self) {
+        if self.recording.is_some() {
+            self.stop_recording();
+        } else if let Some(e) = self.selected_entity {
+            let id = body_id(&self.world, e).unwrap_or_default();
+            self.recording = Some(Recording {
+                entity: e,
+                path: format!("trajectory_{}_{}.csv", id.0, self.sim_steps),
+                rows: Vec::new(),
+            });
+        }
+    }
+
+    // writes out what's been recorded so far, the recording carries on
+    pub fn export_recording(&mut self) {
+        if let Some(recording) = &self.recording {
+            self.imgui_wrapper.render_data.status =
+                match export_trajectory(&recording.path, &recording.rows) {
+                    Ok(()) => format!("Saved {}", recording.path),
+                    Err(e) => format!("Error writing {}: {}", recording.path, e),
+                };
+        }
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.export_recording();
+        self.recording = None;
+    }
+
+    // the recording stops if its body is deselected or destroyed
+    fn record_step(&mut self, step: usize) {
+        let entity = match &self.recording {
+            Some(recording) => recording.entity,
+            None => return,
+        };
+
+        let row = {
+            let positions = self.world.read_storage::<Position>();
+            let kinematics = self.world.read_storage::<Kinematics>();
+            match (positions.get(entity), kinematics.get(entity)) {
+                (Some(pos), Some(kine)) if self.selected_entity == Some(entity) => {
+                    Some((step, pos.0, kine.vel))
+                }
+                _ => None,
+            }
+        };
+
+        match (row, &mut self.recording) {
+            (Some(row), Some(recording)) => recording.rows.push(row),
+            _ => self.stop_recording(),
+        }
+    }
+
+    // Each notch of the wheel zooms further if the last one was recent, so fast scrolling covers
+    // large zoom changes. With smoothing the zoom is eased in over the next few frames.
+    pub fn scroll_zoom(&mut self, ctx: &mut Context, y: f32) {
+        let fast = self.last_scroll.elapsed().as_secs_f32() < 0.1;
+        self.scroll_accel = if fast {
+            (self.scroll_accel * 1.5).min(4.0)
+        } else {
+            1.0
+        };
+        self.last_scroll = Instant::now();
+
+        let step = y * ZOOM_STEP * self.scroll_accel;
+        let focus = input::mouse::position(ctx).into();
+
+        if self.world.fetch::<ZoomSmoothing>().0 {
+            self.zoom_velocity += step;
+            self.zoom_focus = focus;
+        } else {
+            let resolution = self.world.fetch::<Resolution>().0;
+            zoom_about(ctx, focus, 1.0 - step, resolution);
+        }
+    }
+
+    pub fn update_zoom(&mut self, ctx: &mut Context) {
+        if self.zoom_velocity.abs() > 1e-4 {
+            let step = self.zoom_velocity * ZOOM_EASE;
+            let resolution = self.world.fetch::<Resolution>().0;
+            zoom_about(ctx, self.zoom_focus, 1.0 - step, resolution);
+            self.zoom_velocity -= step;
+        } else {
+            self.zoom_velocity = 0.0;
+        }
+    }
+
+    // resizes the same way as when the window is resized by hand, so bodies keep their aspect ratio
+    pub fn toggle_fullscreen(&mut self, ctx: &mut Context) {
+        self.world.get_mut::<Fullscreen>().unwrap().toggle();
+        let fullscreen_type = if self.world.fetch::<Fullscreen>().0 {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Windowed
+        };
+        graphics::set_fullscreen(ctx, fullscreen_type).expect("error toggling fullscreen");
+
+        let (width, height) = graphics::size(ctx);
+        self.resize_event(ctx, width, height);
+    }
+
+    // back to the view the sim starts with, stops following and any zoom still easing in
+    pub fn reset_camera(&mut self, ctx: &mut Context) {
+        self.world.insert(FollowSelectedBody(false));
+        self.zoom_velocity = 0.0;
+
+        let resolution = self.world.fetch::<Resolution>().0;
+        let aspect_ratio = resolution.y / resolution.x;
+        graphics::set_screen_coordinates(
+            ctx,
+            graphics::Rect::new(0.0, 0.0, crate::SCREEN_X, crate::SCREEN_Y * aspect_ratio),
+        )
+        .expect("error resetting camera");
+    }
+
+    // deletes the newest body made with the create tool that hasn't since collided
+    pub fn undo_create(&mut self) {
+        while let Some(e) = self.created_bodies.pop_back() {
+            if self.world.is_alive(e) {
+                if self.selected_entity == Some(e) {
+                    self.world.insert(FollowSelectedBody(false));
+                    self.selected_entity = None;
+                    self.imgui_wrapper.remove_sidemenu();
+                    self.imgui_wrapper.render_data.entity_selected = false;
+                }
+                self.world
+                    .delete_entity(e)
+                    .expect("error deleting created body");
+                return;
+            }
+        }
+    }
+
+    // starts a measurement at the body under the mouse, or the point if there isn't one
+    pub fn start_measurement(&mut self, ctx: &Context, x: f32, y: f32) {
+        let coords = graphics::screen_coordinates(ctx);
+        let resolution = self.world.fetch::<Resolution>().0;
+        self.measure_start = Some(scale_pos([x, y], coords, resolution));
+        self.measure_start_body = self.body_at(ctx, x, y);
+    }
+
+    // Sets the timestep from the [ and ] keys, straight into the world as well as the menu so the
+    // orbit prediction drawn this frame already uses it
+    pub fn set_dt(&mut self, dt: f32) {
+        let dt = dt.max(MIN_DT).min(MAX_DT);
+        self.imgui_wrapper.render_data.dt = dt;
+        self.world.insert(DT(dt));
+    }
+
+    // Selects the body after the selected one in entity order, or before it if forward is false,
+    // wrapping around. Starts from the first or last body if nothing's selected.
+    pub fn cycle_selection(&mut self, forward: bool) {
+        let bodies: Vec<Entity> = (&self.world.entities(), &self.world.read_storage::<Mass>())
+            .join()
+            .map(|(entity, _)| entity)
+            .collect();
+        if bodies.is_empty() {
+            return;
+        }
+
+        let current = self
+            .selected_entity
+            .and_then(|e| bodies.iter().position(|body| *body == e));
+        let next = match (current, forward) {
+            (Some(i), true) => (i + 1) % bodies.len(),
+            (Some(i), false) => (i + bodies.len() - 1) % bodies.len(),
+            (None, true) => 0,
+            (None, false) => bodies.len() - 1,
+        };
+        self.select(Some(bodies[next]));
+    }
+
+    // Copies the selected body just far enough to its right that the two won't merge, and selects
+    // the copy. Its trail starts empty.
+    pub fn duplicate_selected(&mut self) {
+        let e = match self.selected_entity {
+            Some(e) if self.world.is_alive(e) => e,
+            _ => return,
+        };
+
+        let body = {
+            let positions = self.world.read_storage::<Position>();
+            let kinematics = self.world.read_storage::<Kinematics>();
+            let masses = self.world.read_storage::<Mass>();
+            let draws = self.world.read_storage::<Draw>();
+            let radii = self.world.read_storage::<Radius>();
+            let trails = self.world.read_storage::<Trail>();
+            match (
+                positions.get(e),
+                kinematics.get(e),
+                masses.get(e),
+                draws.get(e),
+                radii.get(e),
+                trails.get(e),
+            ) {
+                (Some(pos), Some(kine), Some(mass), Some(draw), Some(rad), Some(trail)) => {
+                    let gap = 2.0 * rad.0 * self.world.fetch::<MergeThreshold>().0.max(1.0);
+                    let mut body =
+                        new_body(pos.0 + Vector::new(gap * 1.1, 0.0), kine.vel, mass.0, rad.0);
+                    body.3 = *draw;
+                    body.5 = Trail {
+                        enabled: trail.enabled,
+                        color: trail.color,
+                        ..Trail::new(trail.max_len)
+                    };
+                    body
+                }
+                _ => return,
+            }
+        };
+
+        let copy = create_body(&mut self.world, body);
+        self.select(Some(copy));
+    }
+
+    // adds delta to the selected body's velocity, this works while paused so an orbit can be tuned
+    // before resuming
+    pub fn nudge_selected(&mut self, delta: Vector) {
+        if let Some(e) = self.selected_entity {
+            if let Some(kine) = self.world.write_storage::<Kinematics>().get_mut(e) {
+                kine.vel += delta;
+            }
+        }
+    }
+
+    // centers the view on target without changing the zoom
+    pub fn pan_to(&mut self, ctx: &mut Context, target: Point) {
+        self.world.insert(FollowSelectedBody(false));
+
+        let mut screen_coordinates = graphics::screen_coordinates(ctx);
+        screen_coordinates.x = target.x - screen_coordinates.w / 2.0;
+        screen_coordinates.y = target.y - screen_coordinates.h / 2.0;
+        graphics::set_screen_coordinates(ctx, screen_coordinates).expect("error panning camera");
+    }
+
+    pub fn pan_to_center_of_mass(&mut self, ctx: &mut Context) {
+        if let Some(com) = center_of_mass(&self.world) {
+            self.pan_to(ctx, com);
+        }
+    }
+
+    // frames every body, or resets the camera if there aren't any
+    pub fn zoom_to_fit(&mut self, ctx: &mut Context) {
+        let bounds = match bounds_of(&self.world) {
+            Some(bounds) => bounds,
+            None => return self.reset_camera(ctx),
+        };
+
+        self.world.insert(FollowSelectedBody(false));
+        self.zoom_velocity = 0.0;
+
+        let resolution = self.world.fetch::<Resolution>().0;
+        let aspect_ratio = resolution.y / resolution.x;
+        let width = bounds.w.max(bounds.h / aspect_ratio) * FIT_MARGIN;
+        let width = clamp_zoom(width / crate::SCREEN_X) * crate::SCREEN_X;
+        let height = width * aspect_ratio;
+
+        let center = Point::new(bounds.x + bounds.w / 2.0, bounds.y + bounds.h / 2.0);
+        graphics::set_screen_coordinates(
+            ctx,
+            graphics::Rect::new(
+                center.x - width / 2.0,
+                center.y - height / 2.0,
+                width,
+                height,
+            ),
+        )
+        .expect("error fitting camera");
+    }
+
+    pub fn process_gui_signals(&mut self, ctx: &mut Context) {
         self.imgui_wrapper
             .sent_signals
             .clone()
             .iter()
             .for_each(|signal| match signal {
-                UiSignal::Create => self.creating = !self.creating,
+                UiSignal::SpawnPreset(preset) => {
+                    self.world.delete_all();
+                    self.reset_stats();
+                    self.perturbed_world = None;
+
+                    let coords = graphics::screen_coordinates(ctx);
+                    spawn_preset(&mut self.world, *preset, coords);
+                }
+                UiSignal::SetTool(tool) => {
+                    self.tool = *tool;
+                    self.measure_start = None;
+                    self.measure_start_body = None;
+                    self.moving_entity = None;
+                }
                 UiSignal::Delete => {
                     if let Some(e) = self.selected_entity {
                         self.world.insert(FollowSelectedBody(false));
+                        // the trail and any graphs are components of the entity so they go with it
                         self.world
                             .delete_entity(e)
                             .expect("error deleting selected_entity");
                         self.selected_entity = None;
+                        self.imgui_wrapper.remove_sidemenu();
+                        self.imgui_wrapper.render_data.entity_selected = false;
                     }
                 }
                 UiSignal::AddGraph(graph_type) => {
@@ -112,7 +669,8 @@ impl<'a, 'b> MainState<'a, 'b> {
                             [GraphType::Speed, SpeedGraph],
                             [GraphType::XVel, XVelGraph],
                             [GraphType::YVel, YVelGraph],
-                            [GraphType::Accel, AccelGraph]
+                            [GraphType::Accel, AccelGraph],
+                            [GraphType::AccelMag, AccelMagGraph]
                         );
                         if !self.imgui_wrapper.shown_menus.contains(&UiChoice::Graph) {
                             self.imgui_wrapper.shown_menus.insert(UiChoice::Graph);
@@ -130,7 +688,7 @@ impl<'a, 'b> MainState<'a, 'b> {
                             )*
                         };
                     }
-                    undisplay_graphs!(SpeedGraph, XVelGraph, YVelGraph, AccelGraph);
+                    undisplay_graphs!(SpeedGraph, XVelGraph, YVelGraph, AccelGraph, AccelMagGraph);
                 }
                 UiSignal::SaveState => {
                     match save_world(
@@ -146,6 +704,8 @@ impl<'a, 'b> MainState<'a, 'b> {
                 }
                 UiSignal::LoadState => {
                     self.world.delete_all();
+                    self.reset_stats();
+                    self.perturbed_world = None;
                     match load_world(
                         &self.world,
                         format!(
@@ -159,10 +719,33 @@ impl<'a, 'b> MainState<'a, 'b> {
                 }
                 UiSignal::DeleteAll => {
                     self.world.delete_all();
+                    self.reset_stats();
+                    self.perturbed_world = None;
                 }
+                UiSignal::Perturb => {
+                    self.perturb(self.imgui_wrapper.render_data.perturb_epsilon);
+                }
+                UiSignal::ClearPerturbation => {
+                    self.perturbed_world = None;
+                }
+                UiSignal::ApplyKick => {
+                    apply_linear_kick(&mut self.world, self.imgui_wrapper.render_data.kick.into());
+                }
+                UiSignal::ApplySpin => {
+                    apply_spin(&mut self.world, self.imgui_wrapper.render_data.spin);
+                }
+                UiSignal::ToggleRecording => self.toggle_recording(),
+                UiSignal::ExportTrajectory => self.export_recording(),
                 UiSignal::ToggleFollowBody => {
                     self.world.get_mut::<FollowSelectedBody>().unwrap().toggle();
                 }
+                UiSignal::ClearTrail => {
+                    if let Some(e) = self.selected_entity {
+                        if let Some(trail) = self.world.write_storage::<Trail>().get_mut(e) {
+                            trail.points.clear();
+                        }
+                    }
+                }
                 UiSignal::ToggleTrails => {
                     self.world.get_mut::<EnableTrails>().unwrap().toggle();
                 }
@@ -174,6 +757,43 @@ impl<'a, 'b> MainState<'a, 'b> {
                             trail.points.clear();
                         });
                 }
+                UiSignal::ToggleTrailScope => {
+                    self.world.get_mut::<TrailScope>().unwrap().toggle();
+                }
+                UiSignal::ToggleLogRadius => {
+                    self.world.get_mut::<RadiusDisplayMode>().unwrap().toggle();
+                }
+                UiSignal::ToggleSpeedColors => {
+                    self.world.get_mut::<ColorDisplayMode>().unwrap().toggle();
+                }
+                UiSignal::ToggleTrailTaper => {
+                    self.world.get_mut::<TrailTaper>().unwrap().toggle();
+                }
+                UiSignal::ToggleTrailFade => {
+                    self.world.get_mut::<TrailStyle>().unwrap().toggle();
+                }
+                UiSignal::ToggleMetaballs => {
+                    self.world.get_mut::<Metaballs>().unwrap().toggle();
+                }
+                UiSignal::TogglePrediction => {
+                    self.world.get_mut::<PredictSelected>().unwrap().toggle();
+                }
+                UiSignal::ToggleCenterOfMass => {
+                    self.world.get_mut::<ShowCenterOfMass>().unwrap().toggle();
+                }
+                UiSignal::ToggleIsolateSelected => {
+                    self.world.get_mut::<IsolateSelected>().unwrap().toggle();
+                }
+                UiSignal::ToggleStats => {
+                    if !self.imgui_wrapper.shown_menus.remove(&UiChoice::Stats) {
+                        self.imgui_wrapper.shown_menus.insert(UiChoice::Stats);
+                    }
+                }
+                UiSignal::ToggleTimings => {
+                    if !self.imgui_wrapper.shown_menus.remove(&UiChoice::Timings) {
+                        self.imgui_wrapper.shown_menus.insert(UiChoice::Timings);
+                    }
+                }
                 UiSignal::Pause => {
                     self.world.get_mut::<Paused>().unwrap().toggle();
                 }
@@ -181,6 +801,14 @@ impl<'a, 'b> MainState<'a, 'b> {
         self.imgui_wrapper.sent_signals.clear();
     }
 
+    pub fn reset_stats(&mut self) {
+        self.world.insert(SystemStats::default());
+        self.world.insert(CollisionLog::default());
+        self.world.insert(DiagnosticsLog::default());
+        self.sim_time = 0.0;
+        self.sim_steps = 0;
+    }
+
     // there's gotta be a better way to do this but its performance doesn't matter
     pub fn delete_preview(&mut self) {
         let mut delset: HashSet<Entity> = HashSet::new();
@@ -198,12 +826,24 @@ impl<'a, 'b> MainState<'a, 'b> {
                 .delete_entity(entity)
                 .expect("error deleting collided preview");
         });
+        self.preview_collision = None;
+        self.steps_since_collision = 0;
     }
 }
 
+// DT stretched by how long the last frame took, capped at MAX_REAL_TIME_SCALE times DT
+pub fn real_time_dt(dt: f32, frame_time: Duration) -> f32 {
+    dt * (frame_time.as_secs_f32() * REAL_TIME_FPS).min(MAX_REAL_TIME_SCALE)
+}
+
 pub fn calc_offset(ctx: &Context) -> Vector {
     let mut offset: Vector = Vector::new(0.0, 0.0);
 
+    // Ctrl+S saves instead of panning
+    if input::keyboard::is_mod_active(ctx, KeyMods::CTRL) {
+        return offset;
+    }
+
     if input::keyboard::is_key_pressed(ctx, KeyCode::Up)
         || input::keyboard::is_key_pressed(ctx, KeyCode::W)
     {
@@ -227,3 +867,65 @@ pub fn calc_offset(ctx: &Context) -> Vector {
 
     offset
 }
+
+pub fn clamp_zoom(zoom: f32) -> f32 {
+    zoom.max(MIN_ZOOM).min(MAX_ZOOM)
+}
+
+// Scales the view by zoom_factor while keeping the point under focus, in window pixels, in place.
+pub fn zoom_about(ctx: &mut Context, focus: Point, zoom_factor: f32, resolution: Vector) {
+    let mut offset = graphics::screen_coordinates(ctx);
+
+    let prev_zoom = offset.w / crate::SCREEN_X;
+    // clamping before the focus math means a zoom at the limit doesn't move the view at all
+    let zoom = clamp_zoom(prev_zoom * zoom_factor);
+
+    let focus: Vector = focus.coords;
+
+    let mut scaled_focus1: Vector = focus;
+    scaled_focus1.x *= offset.w / resolution.x;
+    scaled_focus1.y *= offset.h / resolution.y;
+
+    offset.w = zoom * crate::SCREEN_X;
+    offset.h = zoom * crate::SCREEN_Y / (resolution.x / resolution.y);
+
+    let mut scaled_focus2: Vector = focus;
+    scaled_focus2.x *= offset.w / resolution.x;
+    scaled_focus2.y *= offset.h / resolution.y;
+
+    let delta_focus = scaled_focus2 - scaled_focus1;
+
+    offset.x -= delta_focus.x;
+    offset.y -= delta_focus.y;
+
+    graphics::set_screen_coordinates(ctx, offset).unwrap_or(());
+}
+
+pub fn export_trajectory(path: &str, rows: &[(usize, Point, Vector)]) -> std::io::Result<()> {
+    let csv = rows.iter().fold(
+        String::from(TRAJECTORY_HEADER),
+        |mut csv, (step, pos, vel)| {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                step, pos.x, pos.y, vel.x, vel.y
+            ));
+            csv
+        },
+    );
+    fs::write(path, csv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoom_stays_within_limits() {
+        assert_eq!(clamp_zoom(0.0), MIN_ZOOM);
+        assert_eq!(clamp_zoom(-1.0), MIN_ZOOM);
+        assert_eq!(clamp_zoom(1000.0), MAX_ZOOM);
+        assert_eq!(clamp_zoom(1.0), 1.0);
+        assert_eq!(clamp_zoom(MIN_ZOOM), MIN_ZOOM);
+        assert_eq!(clamp_zoom(MAX_ZOOM), MAX_ZOOM);
+    }
+}
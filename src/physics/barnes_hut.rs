@@ -0,0 +1,270 @@
+use specs::prelude::*;
+
+use crate::ecs::components::{Kinematics, Mass, Position, Radius};
+use crate::ecs::systems::physics_systems::{gravity_accel, GravitySettings};
+use crate::{Point, Vector};
+
+// bodies in exactly the same spot can't be split up, so cells stop subdividing at this depth
+const MAX_DEPTH: usize = 32;
+
+// A square cell of the quadtree. Leaves hold their bodies, branches only hold the total mass
+// and mass weighted position sum of everything below them.
+struct Node {
+    center: Point,
+    half_size: f32,
+    mass: f32,
+    moment: Vector,
+    children: Option<[usize; 4]>,
+    bodies: Vec<(Point, f32)>,
+}
+
+impl Node {
+    fn new(center: Point, half_size: f32) -> Self {
+        Node {
+            center,
+            half_size,
+            mass: 0.0,
+            moment: Vector::new(0.0, 0.0),
+            children: None,
+            bodies: Vec::new(),
+        }
+    }
+
+    fn center_of_mass(&self) -> Point {
+        Point::from(self.moment / self.mass)
+    }
+
+    fn contains(&self, pos: Point) -> bool {
+        (pos.x - self.center.x).abs() <= self.half_size
+            && (pos.y - self.center.y).abs() <= self.half_size
+    }
+
+    fn quadrant(&self, pos: Point) -> usize {
+        match (pos.x >= self.center.x, pos.y >= self.center.y) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (false, true) => 2,
+            (true, true) => 3,
+        }
+    }
+}
+
+// The nodes are kept in one Vec and refer to their children by index, the root is nodes[0].
+pub struct QuadTree {
+    nodes: Vec<Node>,
+}
+
+impl QuadTree {
+    pub fn new(bodies: &[(Point, f32)]) -> Self {
+        let (min, max) = bodies.iter().fold(
+            (
+                Point::new(std::f32::MAX, std::f32::MAX),
+                Point::new(std::f32::MIN, std::f32::MIN),
+            ),
+            |(min, max), (pos, _)| {
+                (
+                    Point::new(min.x.min(pos.x), min.y.min(pos.y)),
+                    Point::new(max.x.max(pos.x), max.y.max(pos.y)),
+                )
+            },
+        );
+
+        let (center, half_size) = if bodies.is_empty() {
+            (Point::new(0.0, 0.0), 1.0)
+        } else {
+            let extent = (max - min).x.max((max - min).y);
+            (min + (max - min) / 2.0, extent / 2.0 + 1.0)
+        };
+
+        let mut tree = QuadTree {
+            nodes: vec![Node::new(center, half_size)],
+        };
+        bodies
+            .iter()
+            .for_each(|(pos, mass)| tree.insert(0, *pos, *mass, 0));
+
+        tree
+    }
+
+    fn insert(&mut self, index: usize, pos: Point, mass: f32, depth: usize) {
+        {
+            let node = &mut self.nodes[index];
+            node.mass += mass;
+            node.moment += pos.coords * mass;
+        }
+
+        if let Some(children) = self.nodes[index].children {
+            let child = children[self.nodes[index].quadrant(pos)];
+            self.insert(child, pos, mass, depth + 1);
+        } else if self.nodes[index].bodies.is_empty() || depth >= MAX_DEPTH {
+            self.nodes[index].bodies.push((pos, mass));
+        } else {
+            self.subdivide(index);
+            let bodies = std::mem::take(&mut self.nodes[index].bodies);
+            let children = self.nodes[index].children.unwrap();
+            bodies
+                .into_iter()
+                .chain(std::iter::once((pos, mass)))
+                .for_each(|(pos, mass)| {
+                    let child = children[self.nodes[index].quadrant(pos)];
+                    self.insert(child, pos, mass, depth + 1);
+                });
+        }
+    }
+
+    fn subdivide(&mut self, index: usize) {
+        let (center, half_size) = (self.nodes[index].center, self.nodes[index].half_size);
+        let quarter = half_size / 2.0;
+
+        let first = self.nodes.len();
+        // same order as Node::quadrant
+        [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+            .iter()
+            .for_each(|(x, y)| {
+                let child_center = center + Vector::new(x * quarter, y * quarter);
+                self.nodes.push(Node::new(child_center, quarter));
+            });
+
+        self.nodes[index].children = Some([first, first + 1, first + 2, first + 3]);
+    }
+
+    // Gravitational acceleration at pos. Bodies exactly at pos are skipped, the same as the brute
+    // force loop skipping the body itself.
+    pub fn accel(&self, pos: Point, theta: f32, g: f32, softening: f32) -> Vector {
+        let mut accel = Vector::new(0.0, 0.0);
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.mass == 0.0 {
+                continue;
+            }
+
+            match node.children {
+                None => node.bodies.iter().for_each(|(other_pos, other_mass)| {
+                    accel += pull(pos, *other_pos, *other_mass, g, softening);
+                }),
+                Some(children) => {
+                    let com = node.center_of_mass();
+                    let dist = (com - pos).norm();
+                    // a cell is never approximated from inside, it could include pos itself
+                    if !node.contains(pos) && node.half_size * 2.0 < theta * dist {
+                        accel += pull(pos, com, node.mass, g, softening);
+                    } else {
+                        stack.extend_from_slice(&children);
+                    }
+                }
+            }
+        }
+
+        accel
+    }
+}
+
+fn pull(pos: Point, other_pos: Point, other_mass: f32, g: f32, softening: f32) -> Vector {
+    if pos == other_pos {
+        return Vector::new(0.0, 0.0);
+    }
+
+    gravity_accel(other_pos - pos, other_mass, g, softening)
+}
+
+// the same as physics_systems::apply_gravity but through a quadtree built once per step
+pub fn apply_gravity(
+    positions: &WriteStorage<'_, Position>,
+    kinematics: &mut WriteStorage<'_, Kinematics>,
+    radii: &WriteStorage<'_, Radius>,
+    masses: &WriteStorage<'_, Mass>,
+    bodies: &BitSet,
+    theta: f32,
+    settings: GravitySettings,
+) {
+    let GravitySettings { g, softening } = settings;
+    microprofile::scope!("Physics-System", "apply_gravity_barnes_hut");
+    let tree = {
+        let points: Vec<(Point, f32)> = (positions, masses)
+            .join()
+            .map(|(pos, mass)| (pos.0, mass.0))
+            .collect();
+        QuadTree::new(&points)
+    };
+
+    let accumulate = |(pos, kinematics, _, _): (&Position, &mut Kinematics, &Radius, u32)| {
+        kinematics.accel = tree.accel(pos.0, theta, g, softening);
+    };
+
+    #[cfg(feature = "parallel")]
+    (positions, kinematics, radii, bodies)
+        .par_join()
+        .for_each(accumulate);
+    #[cfg(not(feature = "parallel"))]
+    (positions, kinematics, radii, bodies)
+        .join()
+        .for_each(accumulate);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::entities::create_body;
+    use crate::ecs::graphics::Rect;
+    use crate::ecs::systems::physics_systems;
+    use crate::ecs::world::new_world;
+    use crate::presets::random_bodies;
+
+    #[test]
+    fn matches_brute_force_as_theta_goes_to_0() {
+        let mut world = new_world();
+        random_bodies(50, 7, Rect::new(0.0, 0.0, 100.0, 100.0))
+            .into_iter()
+            .for_each(|body| {
+                create_body(&mut world, body);
+            });
+        let settings = GravitySettings {
+            g: 1.2,
+            softening: 1.0,
+        };
+
+        let accels = |world: &World, barnes_hut: bool| -> Vec<Vector> {
+            let (positions, mut kinematics, radii, masses, entities): (
+                WriteStorage<Position>,
+                WriteStorage<Kinematics>,
+                WriteStorage<Radius>,
+                WriteStorage<Mass>,
+                Entities,
+            ) = world.system_data();
+            let bodies = kinematics.mask().clone();
+            if barnes_hut {
+                apply_gravity(
+                    &positions,
+                    &mut kinematics,
+                    &radii,
+                    &masses,
+                    &bodies,
+                    0.01,
+                    settings,
+                );
+            } else {
+                physics_systems::apply_gravity(
+                    &positions,
+                    &mut kinematics,
+                    &radii,
+                    &masses,
+                    &entities,
+                    &bodies,
+                    settings,
+                );
+            }
+            kinematics.join().map(|kine| kine.accel).collect()
+        };
+
+        let brute_force = accels(&world, false);
+        let barnes_hut = accels(&world, true);
+        brute_force
+            .iter()
+            .zip(barnes_hut.iter())
+            .for_each(|(exact, approx)| {
+                assert!((exact - approx).norm() <= 1e-4 * exact.norm().max(1e-3));
+            });
+    }
+}
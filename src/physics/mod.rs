@@ -0,0 +1,1163 @@
+use specs::prelude::*;
+use specs::saveload::SimpleMarker;
+
+use crate::ecs::{
+    components::{
+        Aggregate, Anchored, BodyId, Kinematics, Mass, Position, Preview, Radius, SaveMarker, Trail,
+    },
+    entities::create_body,
+    graphics::Rect,
+    resources::{
+        BarnesHutTheta, Boundary, CollisionEvent, CollisionIterations, CollisionLog, CollisionMode,
+        Density, DiagnosticsLog, EnableTrails, GravityConstant, GravityMode, InspiralDamping,
+        Integrator, IsolateSelected, MaxBodyMass, MaxSpeed, MaxTrailPoints, MergeThreshold,
+        NanPolicy, NonFiniteEvent, PhysicsTimings, SelectedEntity, Softening, StickThreshold,
+        Substeps, SystemStats, WorldSize, DT,
+    },
+    systems::{
+        physics_systems,
+        trail_sys::{self, TrailSys},
+    },
+    world::clone_bodies,
+};
+use crate::{Point, Vector};
+
+use std::time::{Duration, Instant};
+
+pub mod barnes_hut;
+
+// Each step runs a single phase of the physics pipeline over every body in the world, so they
+// can be composed into a custom loop, e.g. gravity without collisions.
+// do_physics is the composition used by the main loop.
+
+pub fn do_physics(world: &mut World) {
+    // the pipeline runs once per substep of DT / Substeps, trails only get a point per whole step
+    let substeps = world.fetch::<Substeps>().0.max(1);
+    let dt = world.fetch::<DT>().0;
+    world.insert(DT(dt / substeps as f32));
+    let timed = world.fetch::<PhysicsTimings>().enabled;
+
+    for _ in 0..substeps {
+        time_phase(world, timed, integrate_positions, |t| {
+            &mut t.integrate_positions
+        });
+        apply_boundary(world);
+        // before gravity can spread a bad position to every other body
+        validate_bodies(world);
+        time_phase(world, timed, apply_gravity, |t| &mut t.apply_gravity);
+        time_phase(world, timed, integrate_kinematics, |t| {
+            &mut t.integrate_kinematics
+        });
+        apply_inspiral(world);
+        integrate_aggregates(world);
+        validate_bodies(world);
+
+        let collision_iterations = world.fetch::<CollisionIterations>().0.max(1);
+        for _ in 0..collision_iterations {
+            time_phase(world, timed, calc_collisions, |t| &mut t.calc_collisions);
+            world.maintain();
+        }
+    }
+
+    world.insert(DT(dt));
+    time_phase(world, timed, update_trails, |t| &mut t.update_trails);
+    world.maintain();
+}
+
+// runs phase, and if timed adds how long it took to its time in PhysicsTimings
+fn time_phase(
+    world: &mut World,
+    timed: bool,
+    phase: fn(&mut World),
+    time: fn(&mut PhysicsTimings) -> &mut Duration,
+) {
+    if !timed {
+        phase(world);
+        return;
+    }
+
+    let start = Instant::now();
+    phase(world);
+    *time(&mut world.fetch_mut::<PhysicsTimings>()) += start.elapsed();
+}
+
+// Copies every resource that changes how do_physics steps from one world to another, so a copy of
+// the world evolves the same way.
+pub fn copy_physics_settings(from: &World, to: &mut World) {
+    to.insert(*from.fetch::<DT>());
+    to.insert(*from.fetch::<GravityConstant>());
+    to.insert(*from.fetch::<Softening>());
+    to.insert(*from.fetch::<Integrator>());
+    to.insert(*from.fetch::<GravityMode>());
+    to.insert(*from.fetch::<BarnesHutTheta>());
+    to.insert(*from.fetch::<CollisionIterations>());
+    to.insert(*from.fetch::<Substeps>());
+    to.insert(*from.fetch::<CollisionMode>());
+    to.insert(*from.fetch::<Boundary>());
+    to.insert(*from.fetch::<WorldSize>());
+    to.insert(*from.fetch::<MaxBodyMass>());
+    to.insert(*from.fetch::<MergeThreshold>());
+    to.insert(*from.fetch::<StickThreshold>());
+    to.insert(*from.fetch::<MaxSpeed>());
+    to.insert(*from.fetch::<Density>());
+    to.insert(*from.fetch::<InspiralDamping>());
+    to.insert(*from.fetch::<NanPolicy>());
+}
+
+// Where entity will be over the next steps, found by stepping a copy of the world so the world
+// itself isn't touched. Stops early if the body collides.
+pub fn predict_path(world: &World, entity: Entity, steps: usize) -> Vec<Point> {
+    let (mut scratch, copies) = clone_bodies(world);
+    let copy = match copies.get(&entity) {
+        Some(copy) => *copy,
+        None => return Vec::new(),
+    };
+    copy_physics_settings(world, &mut scratch);
+    scratch.insert(EnableTrails(false));
+
+    let mut path = Vec::with_capacity(steps);
+    for _ in 0..steps {
+        do_physics(&mut scratch);
+        match scratch.read_storage::<Position>().get(copy) {
+            Some(pos) => path.push(pos.0),
+            None => break,
+        }
+    }
+
+    path
+}
+
+// The bodies which are moved by gravity and the integrators. Frozen and anchored bodies still
+// attract the others.
+fn moving_bodies(world: &World) -> BitSet {
+    let kinematics = world.read_storage::<Kinematics>();
+    let anchors = world.read_storage::<Anchored>();
+
+    // if IsolateSelected is on, only the selected body moves through the field of the others
+    if world.fetch::<IsolateSelected>().0 {
+        if let Some(e) = world.fetch::<SelectedEntity>().0 {
+            if kinematics.contains(e) {
+                let mut bodies = BitSet::new();
+                if !anchors.contains(e) {
+                    bodies.add(e.id());
+                }
+                return bodies;
+            }
+        }
+    }
+
+    (kinematics.mask() & !anchors.mask()).join().collect()
+}
+
+// with Integrator::RK4 this steps the velocities too
+pub fn integrate_positions(world: &mut World) {
+    let bodies = moving_bodies(world);
+    let (mut positions, mut kinematics, masses, entities, dt, integrator, g, softening): (
+        WriteStorage<Position>,
+        WriteStorage<Kinematics>,
+        WriteStorage<Mass>,
+        Entities,
+        Read<DT>,
+        Read<Integrator>,
+        Read<GravityConstant>,
+        Read<Softening>,
+    ) = world.system_data();
+
+    if *integrator == Integrator::RK4 {
+        physics_systems::integrate_rk4(
+            &mut positions,
+            &mut kinematics,
+            &masses,
+            &entities,
+            &bodies,
+            dt.0,
+            physics_systems::GravitySettings {
+                g: g.0,
+                softening: softening.0,
+            },
+        );
+    } else {
+        physics_systems::integrate_positions(
+            &mut positions,
+            &kinematics,
+            &bodies,
+            dt.0,
+            *integrator,
+        );
+    }
+}
+
+// does nothing when the Boundary is None
+pub fn apply_boundary(world: &mut World) {
+    let bodies = moving_bodies(world);
+    let (mut positions, mut kinematics, radii, boundary, world_size): (
+        WriteStorage<Position>,
+        WriteStorage<Kinematics>,
+        WriteStorage<Radius>,
+        Read<Boundary>,
+        Read<WorldSize>,
+    ) = world.system_data();
+
+    physics_systems::apply_boundary(
+        &mut positions,
+        &mut kinematics,
+        &radii,
+        &bodies,
+        *boundary,
+        world_size.0,
+    );
+}
+
+pub fn apply_gravity(world: &mut World) {
+    let bodies = moving_bodies(world);
+    let (positions, mut kinematics, radii, masses, entities, g, softening): (
+        WriteStorage<Position>,
+        WriteStorage<Kinematics>,
+        WriteStorage<Radius>,
+        WriteStorage<Mass>,
+        Entities,
+        Read<GravityConstant>,
+        Read<Softening>,
+    ) = world.system_data();
+    let settings = physics_systems::GravitySettings {
+        g: g.0,
+        softening: softening.0,
+    };
+
+    match *world.fetch::<GravityMode>() {
+        GravityMode::BruteForce => physics_systems::apply_gravity(
+            &positions,
+            &mut kinematics,
+            &radii,
+            &masses,
+            &entities,
+            &bodies,
+            settings,
+        ),
+        GravityMode::BarnesHut => barnes_hut::apply_gravity(
+            &positions,
+            &mut kinematics,
+            &radii,
+            &masses,
+            &bodies,
+            world.fetch::<BarnesHutTheta>().0,
+            settings,
+        ),
+    }
+}
+
+pub fn integrate_kinematics(world: &mut World) {
+    let bodies = moving_bodies(world);
+    let (mut kinematics, dt, integrator, max_speed): (
+        WriteStorage<Kinematics>,
+        Read<DT>,
+        Read<Integrator>,
+        Read<MaxSpeed>,
+    ) = world.system_data();
+
+    physics_systems::integrate_kinematics(&mut kinematics, &bodies, dt.0, *integrator, max_speed.0);
+}
+
+// does nothing unless InspiralDamping is above 0
+pub fn apply_inspiral(world: &mut World) {
+    let strength = world.fetch::<InspiralDamping>().0;
+    if strength <= 0.0 {
+        return;
+    }
+
+    let bodies = moving_bodies(world);
+    let dissipated = {
+        let (positions, mut kinematics, masses, entities, g, dt): (
+            WriteStorage<Position>,
+            WriteStorage<Kinematics>,
+            WriteStorage<Mass>,
+            Entities,
+            Read<GravityConstant>,
+            Read<DT>,
+        ) = world.system_data();
+
+        physics_systems::apply_inspiral(
+            &positions,
+            &mut kinematics,
+            &masses,
+            &entities,
+            &bodies,
+            strength * g.0.abs() * dt.0,
+        )
+    };
+
+    world.fetch_mut::<SystemStats>().dissipated += dissipated;
+}
+
+// aggregates are kept rigid after the members are integrated separately
+pub fn integrate_aggregates(world: &mut World) {
+    let (mut positions, mut kinematics, masses, mut aggregates, dt): (
+        WriteStorage<Position>,
+        WriteStorage<Kinematics>,
+        WriteStorage<Mass>,
+        WriteStorage<Aggregate>,
+        Read<DT>,
+    ) = world.system_data();
+
+    physics_systems::integrate_aggregates(
+        &mut positions,
+        &mut kinematics,
+        &masses,
+        &mut aggregates,
+        dt.0,
+    );
+}
+
+// A very close encounter can leave a body with a NaN or infinite position or velocity, which
+// would spread to every other body through gravity. They're logged and handled by NanPolicy.
+pub fn validate_bodies(world: &mut World) {
+    let policy = *world.fetch::<NanPolicy>();
+    let invalid: Vec<(Entity, BodyId)> = {
+        let (positions, kinematics, markers, entities): (
+            ReadStorage<Position>,
+            ReadStorage<Kinematics>,
+            ReadStorage<SimpleMarker<SaveMarker>>,
+            Entities,
+        ) = world.system_data();
+
+        (&entities, &positions, &kinematics)
+            .join()
+            .filter(|(_, pos, kine)| !(is_finite(&pos.0.coords) && is_finite(&kine.vel)))
+            .map(|(e, _, _)| (e, markers.get(e).map(BodyId::from).unwrap_or_default()))
+            .collect()
+    };
+    if invalid.is_empty() {
+        return;
+    }
+
+    {
+        let mut log = world.fetch_mut::<DiagnosticsLog>();
+        invalid.iter().for_each(|(_, body)| {
+            log.push(NonFiniteEvent {
+                body: *body,
+                policy,
+            })
+        });
+    }
+
+    match policy {
+        NanPolicy::Delete => invalid.into_iter().for_each(|(e, _)| {
+            world
+                .delete_entity(e)
+                .expect("error deleting non-finite entity");
+        }),
+        NanPolicy::Clamp => {
+            let world_size = world.fetch::<WorldSize>().0;
+            let mut positions = world.write_storage::<Position>();
+            let mut kinematics = world.write_storage::<Kinematics>();
+            invalid.into_iter().for_each(|(e, _)| {
+                // NaNs go to the middle of the world and infinities to its edges
+                if let Some(pos) = positions.get_mut(e) {
+                    pos.0.x = clamp_finite(pos.0.x, 0.0, world_size.x);
+                    pos.0.y = clamp_finite(pos.0.y, 0.0, world_size.y);
+                }
+                if let Some(kine) = kinematics.get_mut(e) {
+                    kine.vel = kine.vel.map(|v| if v.is_finite() { v } else { 0.0 });
+                    kine.accel = kine.accel.map(|a| if a.is_finite() { a } else { 0.0 });
+                    kine.past_accel = kine.past_accel.map(|a| if a.is_finite() { a } else { 0.0 });
+                }
+            });
+        }
+        NanPolicy::Ignore => {}
+    }
+}
+
+fn is_finite(v: &Vector) -> bool {
+    v.x.is_finite() && v.y.is_finite()
+}
+
+fn clamp_finite(x: f32, min: f32, max: f32) -> f32 {
+    if x.is_nan() {
+        (min + max) / 2.0
+    } else if x == std::f32::INFINITY {
+        max
+    } else if x == std::f32::NEG_INFINITY {
+        min
+    } else {
+        x
+    }
+}
+
+pub fn calc_collisions(world: &mut World) {
+    let collision_mode = *world.fetch::<CollisionMode>();
+    match collision_mode {
+        CollisionMode::Merge => {}
+        CollisionMode::Aggregate => {
+            aggregate_collisions(world);
+            return;
+        }
+        CollisionMode::Bounce => {
+            bounce_collisions(world);
+            return;
+        }
+    }
+
+    let (mut create_vec, deleted, merges, bounces) = {
+        let (
+            positions,
+            kinematics,
+            masses,
+            radii,
+            anchors,
+            entities,
+            merge_threshold,
+            stick_threshold,
+        ): (
+            WriteStorage<Position>,
+            WriteStorage<Kinematics>,
+            WriteStorage<Mass>,
+            WriteStorage<Radius>,
+            ReadStorage<Anchored>,
+            Entities,
+            Read<MergeThreshold>,
+            Read<StickThreshold>,
+        ) = world.system_data();
+
+        physics_systems::calc_collisions(
+            &positions,
+            &kinematics,
+            &masses,
+            &radii,
+            &anchors,
+            &entities,
+            physics_systems::MergeSettings {
+                merge_threshold: merge_threshold.0,
+                stick_threshold: stick_threshold.0,
+            },
+        )
+    };
+
+    // before the merged bodies are deleted so every bounced pair is still there
+    bounce_contacts(world, bounces);
+
+    {
+        let (markers, mut collision_log, mut stats): (
+            ReadStorage<SimpleMarker<SaveMarker>>,
+            Write<CollisionLog>,
+            Write<SystemStats>,
+        ) = world.system_data();
+        // every body is marked when it's created so the default is never used
+        let id = |e: Entity| markers.get(e).map(BodyId::from).unwrap_or_default();
+        merges.into_iter().for_each(|(e1, e2, dissipated)| {
+            stats.dissipated += dissipated;
+            collision_log.push(CollisionEvent {
+                bodies: (id(e1), id(e2)),
+                dissipated,
+            });
+        });
+    }
+
+    deleted.into_iter().for_each(|e| {
+        world
+            .delete_entity(e)
+            .expect("error deleting collided entity");
+    });
+
+    // anchors stay whole so they stay where they are
+    if let Some(max_mass) = world.fetch::<MaxBodyMass>().0 {
+        let g = world.fetch::<GravityConstant>().0;
+        create_vec = create_vec
+            .into_iter()
+            .flat_map(|(body, anchored)| {
+                if anchored {
+                    vec![(body, anchored)]
+                } else {
+                    physics_systems::fragment(body, max_mass, g)
+                        .into_iter()
+                        .map(|fragment| (fragment, false))
+                        .collect()
+                }
+            })
+            .collect();
+    }
+
+    // the merged bodies' radii follow their mass instead of their combined volume
+    let density = *world.fetch::<Density>();
+    create_vec.iter_mut().for_each(|(body, _)| {
+        if let Some(rad) = density.radius((body.2).0) {
+            (body.4).0 = rad;
+        }
+    });
+
+    // create_body marks the merged bodies so they're included in saves
+    create_vec.drain(..).for_each(|(body, anchored)| {
+        let entity = create_body(world, body);
+        if anchored {
+            world
+                .write_storage::<Anchored>()
+                .insert(entity, Anchored)
+                .expect("error anchoring merged body");
+        }
+    });
+}
+
+// Bodies that touch stick together into an aggregate instead of merging, touching bodies from
+// different aggregates join them into one. Groups are identified by the id of one of their bodies.
+fn aggregate_collisions(world: &mut World) {
+    let contacts = {
+        let (positions, radii, masses, aggregates, entities): (
+            WriteStorage<Position>,
+            WriteStorage<Radius>,
+            WriteStorage<Mass>,
+            WriteStorage<Aggregate>,
+            Entities,
+        ) = world.system_data();
+
+        physics_systems::find_contacts(&positions, &radii, &masses, &aggregates, &entities)
+    };
+
+    contacts.into_iter().for_each(|(e1, e2)| {
+        let (
+            positions,
+            kinematics,
+            masses,
+            markers,
+            mut aggregates,
+            entities,
+            mut stats,
+            mut log,
+        ): (
+            ReadStorage<Position>,
+            ReadStorage<Kinematics>,
+            ReadStorage<Mass>,
+            ReadStorage<SimpleMarker<SaveMarker>>,
+            WriteStorage<Aggregate>,
+            Entities,
+            Write<SystemStats>,
+            Write<CollisionLog>,
+        ) = world.system_data();
+
+        let body_id = |e: Entity| markers.get(e).map(BodyId::from).unwrap_or_default();
+        // a body that isn't in an aggregate is in a group of its own
+        let group = |e: Entity| match aggregates.get(e) {
+            Some(aggregate) => aggregate.group,
+            None => body_id(e).0,
+        };
+
+        // earlier contacts this step may have already joined them
+        let (g1, g2) = (group(e1), group(e2));
+        if g1 == g2 {
+            return;
+        }
+
+        let members: Vec<(Entity, Point, Vector, f32)> =
+            (&entities, &positions, &kinematics, &masses)
+                .join()
+                .filter(|(e, _, _, _)| [g1, g2].contains(&group(*e)))
+                .map(|(e, pos, kine, mass)| (e, pos.0, kine.vel, mass.0))
+                .collect();
+
+        let total_mass: f32 = members.iter().map(|(_, _, _, mass)| mass).sum();
+        if total_mass == 0.0 {
+            return;
+        }
+        let center = members
+            .iter()
+            .fold(Vector::new(0.0, 0.0), |acc, (_, pos, _, mass)| {
+                acc + pos.coords * *mass
+            })
+            / total_mass;
+        let momentum = members
+            .iter()
+            .fold(Vector::new(0.0, 0.0), |acc, (_, _, vel, mass)| {
+                acc + vel * *mass
+            });
+
+        // the kinetic energy lost is whatever isn't in the motion of the new rigid aggregate
+        let (ke_before, angular_momentum, inertia) =
+            members
+                .iter()
+                .fold((0.0, 0.0, 0.0), |(ke, l, i), (_, pos, vel, mass)| {
+                    let r = pos.coords - center;
+                    let v = vel - momentum / total_mass;
+                    (
+                        ke + 0.5 * mass * vel.norm_squared(),
+                        l + mass * (r.x * v.y - r.y * v.x),
+                        i + mass * r.norm_squared(),
+                    )
+                });
+        let mut ke_after = 0.5 * momentum.norm_squared() / total_mass;
+        if inertia != 0.0 {
+            ke_after += 0.5 * angular_momentum.powi(2) / inertia;
+        }
+
+        let event = CollisionEvent {
+            bodies: (body_id(e1), body_id(e2)),
+            dissipated: ke_before - ke_after,
+        };
+        stats.dissipated += event.dissipated;
+        log.push(event);
+
+        members.into_iter().for_each(|(e, pos, _, _)| {
+            let aggregate = Aggregate {
+                group: g1.min(g2),
+                offset: pos.coords - center,
+                angle: 0.0,
+            };
+            aggregates
+                .insert(e, aggregate)
+                .expect("error inserting aggregate");
+        });
+    });
+}
+
+// touching bodies exchange momentum elastically and are separated, nothing is created or deleted
+fn bounce_collisions(world: &mut World) {
+    let contacts = {
+        let (positions, radii, masses, aggregates, entities): (
+            WriteStorage<Position>,
+            WriteStorage<Radius>,
+            WriteStorage<Mass>,
+            WriteStorage<Aggregate>,
+            Entities,
+        ) = world.system_data();
+
+        physics_systems::find_contacts(&positions, &radii, &masses, &aggregates, &entities)
+    };
+
+    bounce_contacts(world, contacts);
+}
+
+// anchors bounce as if they had infinite mass so they stay where they are
+fn bounce_contacts(world: &mut World, contacts: Vec<(Entity, Entity)>) {
+    let (mut positions, mut kinematics, masses, radii, anchors): (
+        WriteStorage<Position>,
+        WriteStorage<Kinematics>,
+        WriteStorage<Mass>,
+        WriteStorage<Radius>,
+        ReadStorage<Anchored>,
+    ) = world.system_data();
+
+    contacts.into_iter().for_each(|(e1, e2)| {
+        let body = |e: Entity| {
+            let mass = if anchors.contains(e) {
+                Some(std::f32::INFINITY)
+            } else {
+                masses.get(e).map(|mass| mass.0)
+            };
+            (
+                positions.get(e).map(|pos| pos.0),
+                kinematics.get(e).map(|kine| kine.vel),
+                mass,
+                radii.get(e).map(|rad| rad.0),
+            )
+        };
+
+        if let (
+            (Some(pos1), Some(vel1), Some(mass1), Some(rad1)),
+            (Some(pos2), Some(vel2), Some(mass2), Some(rad2)),
+        ) = (body(e1), body(e2))
+        {
+            let ((pos1, vel1), (pos2, vel2)) =
+                physics_systems::bounce((pos1, vel1, mass1, rad1), (pos2, vel2, mass2, rad2));
+
+            positions.get_mut(e1).unwrap().0 = pos1;
+            positions.get_mut(e2).unwrap().0 = pos2;
+            kinematics.get_mut(e1).unwrap().vel = vel1;
+            kinematics.get_mut(e2).unwrap().vel = vel2;
+        }
+    });
+}
+
+pub fn update_trails(world: &mut World) {
+    TrailSys.run_now(world);
+
+    let max_points = world.fetch::<MaxTrailPoints>().0;
+    trail_sys::cap_trail_points(
+        &mut world.write_storage::<Trail>(),
+        &world.read_storage::<Preview>(),
+        max_points,
+    );
+}
+
+// mass weighted average position of every body, None if there are none or they're all massless
+pub fn center_of_mass(world: &World) -> Option<Point> {
+    let positions = world.read_storage::<Position>();
+    let masses = world.read_storage::<Mass>();
+
+    let (weighted_pos, total_mass) = (&positions, &masses).join().fold(
+        (Vector::new(0.0, 0.0), 0.0),
+        |(weighted_pos, total_mass), (pos, mass)| {
+            (weighted_pos + pos.0.coords * mass.0, total_mass + mass.0)
+        },
+    );
+
+    if total_mass != 0.0 {
+        Some(Point::from(weighted_pos / total_mass))
+    } else {
+        None
+    }
+}
+
+// The smallest rectangle containing every body, previews aren't included. None if there are no
+// bodies.
+pub fn bounds_of(world: &World) -> Option<Rect> {
+    let positions = world.read_storage::<Position>();
+    let radii = world.read_storage::<Radius>();
+    let masses = world.read_storage::<Mass>();
+
+    (&positions, &radii, &masses)
+        .join()
+        .map(|(pos, rad, _)| {
+            let rad = Vector::new(rad.0, rad.0);
+            (pos.0 - rad, pos.0 + rad)
+        })
+        .fold(None, |bounds: Option<(Point, Point)>, (min, max)| {
+            Some(bounds.map_or((min, max), |(bounds_min, bounds_max)| {
+                (
+                    Point::new(bounds_min.x.min(min.x), bounds_min.y.min(min.y)),
+                    Point::new(bounds_max.x.max(max.x), bounds_max.y.max(max.y)),
+                )
+            }))
+        })
+        .map(|(min, max)| Rect::new(min.x, min.y, max.x - min.x, max.y - min.y))
+}
+
+// adds delta to the velocity of every body, anchored bodies stay put
+pub fn apply_linear_kick(world: &mut World, delta: Vector) {
+    let (mut kinematics, masses, anchors): (
+        WriteStorage<Kinematics>,
+        ReadStorage<Mass>,
+        ReadStorage<Anchored>,
+    ) = world.system_data();
+
+    (&mut kinematics, &masses, !&anchors)
+        .join()
+        .for_each(|(kine, _, _)| kine.vel += delta);
+}
+
+// Adds a solid body rotation of omega radians per step about the center of mass, so each body
+// gains omega times its distance from it perpendicular to the center. Anchored bodies stay put.
+pub fn apply_spin(world: &mut World, omega: f32) {
+    let center = match center_of_mass(world) {
+        Some(center) => center,
+        None => return,
+    };
+    let (positions, mut kinematics, masses, anchors): (
+        ReadStorage<Position>,
+        WriteStorage<Kinematics>,
+        ReadStorage<Mass>,
+        ReadStorage<Anchored>,
+    ) = world.system_data();
+
+    (&positions, &mut kinematics, &masses, !&anchors)
+        .join()
+        .for_each(|(pos, kine, _, _)| {
+            let r = pos.0 - center;
+            kine.vel += Vector::new(-r.y, r.x) * omega;
+        });
+}
+
+// Whether entity is bound to the rest of the system, and the speed it'd need to escape. Its
+// velocity is taken relative to the rest of the system's mass weighted velocity, and it's bound if
+// its kinetic energy is less than the magnitude of its potential energy from every other body.
+pub fn is_bound(world: &World, entity: Entity) -> (bool, f32) {
+    let (entities, positions, kinematics, masses, g, softening): (
+        Entities,
+        ReadStorage<Position>,
+        ReadStorage<Kinematics>,
+        ReadStorage<Mass>,
+        Read<GravityConstant>,
+        Read<Softening>,
+    ) = world.system_data();
+
+    let (pos, kine) = match (positions.get(entity), kinematics.get(entity)) {
+        (Some(pos), Some(kine)) => (pos.0, kine),
+        _ => return (false, 0.0),
+    };
+
+    let (potential, momentum, other_mass) = (&entities, &positions, &masses)
+        .join()
+        .filter(|(other, _, _)| *other != entity)
+        .fold(
+            (0.0, Vector::new(0.0, 0.0), 0.0),
+            |(potential, momentum, other_mass), (other, other_pos, mass)| {
+                let softened_dist =
+                    ((other_pos.0 - pos).norm_squared() + softening.0.powi(2)).sqrt();
+                let vel = kinematics
+                    .get(other)
+                    .map_or_else(|| Vector::new(0.0, 0.0), |kine| kine.vel);
+                (
+                    potential - g.0 * mass.0 / softened_dist,
+                    momentum + vel * mass.0,
+                    other_mass + mass.0,
+                )
+            },
+        );
+
+    if other_mass == 0.0 {
+        return (false, 0.0);
+    }
+
+    let escape_speed = (-2.0 * potential).sqrt();
+    let rel_speed = (kine.vel - momentum / other_mass).norm();
+    (rel_speed < escape_speed, escape_speed)
+}
+
+// Recalculates everything in SystemStats except dissipated. The potential energy is a sum over
+// every pair of bodies so this is O(n^2), it should only be run when it's needed.
+pub fn update_stats(world: &mut World) {
+    let com = center_of_mass(world).unwrap_or_else(|| Point::new(0.0, 0.0));
+    let (positions, kinematics, masses, g, softening, mut stats): (
+        ReadStorage<Position>,
+        ReadStorage<Kinematics>,
+        ReadStorage<Mass>,
+        Read<GravityConstant>,
+        Read<Softening>,
+        Write<SystemStats>,
+    ) = world.system_data();
+
+    stats.kinetic = (&kinematics, &masses)
+        .join()
+        .map(|(kine, mass)| 0.5 * mass.0 * kine.vel.norm_squared())
+        .sum();
+
+    let bodies: Vec<(Position, f32)> = (&positions, &masses)
+        .join()
+        .map(|(pos, mass)| (*pos, mass.0))
+        .collect();
+
+    stats.potential = bodies
+        .iter()
+        .enumerate()
+        .map(|(i, (pos1, m1))| {
+            bodies[i + 1..]
+                .iter()
+                .map(|(pos2, m2)| {
+                    let softened_dist = (pos1.dist_squared(pos2.0) + softening.0.powi(2)).sqrt();
+                    -g.0 * m1 * m2 / softened_dist
+                })
+                .sum::<f32>()
+        })
+        .sum();
+
+    stats.momentum = (&kinematics, &masses)
+        .join()
+        .fold(Vector::new(0.0, 0.0), |acc, (kine, mass)| {
+            acc + kine.vel * mass.0
+        });
+
+    stats.center_of_mass = com;
+    stats.angular_momentum = (&positions, &kinematics, &masses)
+        .join()
+        .map(|(pos, kine, mass)| {
+            let r = pos.0 - com;
+            mass.0 * (r.x * kine.vel.y - r.y * kine.vel.x)
+        })
+        .sum();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::entities::{new_body, Body};
+    use crate::ecs::world::new_world;
+    use crate::presets::random_bodies;
+
+    fn world_with(bodies: Vec<Body>) -> (World, Vec<Entity>) {
+        let mut world = new_world();
+        let entities = bodies
+            .into_iter()
+            .map(|body| create_body(&mut world, body))
+            .collect();
+        (world, entities)
+    }
+
+    // every body's id, position, velocity and mass
+    fn snapshot(world: &World) -> Vec<(u32, Point, Vector, f32)> {
+        let (entities, positions, kinematics, masses): (
+            Entities,
+            ReadStorage<Position>,
+            ReadStorage<Kinematics>,
+            ReadStorage<Mass>,
+        ) = world.system_data();
+
+        (&entities, &positions, &kinematics, &masses)
+            .join()
+            .map(|(e, pos, kine, mass)| (e.id(), pos.0, kine.vel, mass.0))
+            .collect()
+    }
+
+    const CENTRAL_MASS: f32 = 1000.0;
+    const ORBIT_RADIUS: f32 = 50.0;
+
+    // A light body in a circular orbit around an anchored heavy one. There's no softening so the
+    // orbit is exactly Keplerian.
+    fn orbit_world(integrator: Integrator, dt: f32) -> (World, Entity) {
+        let speed = (crate::G * CENTRAL_MASS / ORBIT_RADIUS).sqrt();
+        let (mut world, entities) = world_with(vec![
+            new_body([0.0, 0.0], [0.0, 0.0], CENTRAL_MASS, 1.0),
+            new_body([ORBIT_RADIUS, 0.0], [0.0, speed], 0.001, 1.0),
+        ]);
+        world
+            .write_storage::<Anchored>()
+            .insert(entities[0], Anchored)
+            .unwrap();
+        world.insert(Softening(0.0));
+        world.insert(integrator);
+        world.insert(DT(dt));
+        (world, entities[1])
+    }
+
+    fn orbit_state(world: &World, e: Entity) -> (Point, Vector) {
+        (
+            world.read_storage::<Position>().get(e).unwrap().0,
+            world.read_storage::<Kinematics>().get(e).unwrap().vel,
+        )
+    }
+
+    // the furthest the orbiting body gets from the circular orbit over steps
+    fn radius_drift(integrator: Integrator, steps: usize) -> f32 {
+        let (mut world, e) = orbit_world(integrator, 0.1);
+        (0..steps)
+            .map(|_| {
+                do_physics(&mut world);
+                let (pos, _) = orbit_state(&world, e);
+                (pos.coords.norm() - ORBIT_RADIUS).abs()
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn verlet_orbits_drift_less_than_euler() {
+        // about five orbits
+        let verlet = radius_drift(Integrator::VelocityVerlet, 3200);
+        let euler = radius_drift(Integrator::Euler, 3200);
+        assert!(verlet < 0.1 * ORBIT_RADIUS);
+        assert!(verlet < euler);
+    }
+
+    // How long the orbiting body takes to get back round to where it started, interpolated
+    // between the steps either side.
+    fn orbital_period(integrator: Integrator, dt: f32) -> f32 {
+        let (mut world, e) = orbit_world(integrator, dt);
+        let mut prev_y = 0.0;
+        for step in 1.. {
+            do_physics(&mut world);
+            let (pos, _) = orbit_state(&world, e);
+            if prev_y < 0.0 && pos.y >= 0.0 {
+                return (step as f32 - pos.y / (pos.y - prev_y)) * dt;
+            }
+            prev_y = pos.y;
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn rk4_tracks_the_kepler_period() {
+        let period =
+            2.0 * std::f32::consts::PI * (ORBIT_RADIUS.powi(3) / (crate::G * CENTRAL_MASS)).sqrt();
+        let rk4_error = (orbital_period(Integrator::RK4, 0.5) - period).abs();
+        let euler_error = (orbital_period(Integrator::Euler, 0.5) - period).abs();
+
+        assert!(rk4_error < 0.001 * period);
+        assert!(rk4_error * 10.0 < euler_error);
+    }
+
+    // the most the orbiting body's energy per unit mass strays from where it started over steps
+    fn energy_drift(substeps: u32, steps: usize) -> f32 {
+        let (mut world, e) = orbit_world(Integrator::VelocityVerlet, 1.0);
+        world.insert(Substeps(substeps));
+        let energy = |world: &World| {
+            let (pos, vel) = orbit_state(world, e);
+            0.5 * vel.norm_squared() - crate::G * CENTRAL_MASS / pos.coords.norm()
+        };
+
+        let start = energy(&world);
+        (0..steps)
+            .map(|_| {
+                do_physics(&mut world);
+                (energy(&world) - start).abs()
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn substeps_reduce_energy_drift() {
+        // about three orbits either way, the substeps don't change the time advanced
+        assert!(energy_drift(8, 200) < energy_drift(1, 200));
+    }
+
+    #[test]
+    fn softening_keeps_close_bodies_finite() {
+        // too small to merge
+        let (mut world, _) = world_with(vec![
+            new_body([0.0, 0.0], [0.0, 0.0], 100.0, 1e-6),
+            new_body([1e-5, 0.0], [0.0, 0.0], 100.0, 1e-6),
+        ]);
+        world.insert(Softening(1.0));
+        (0..10).for_each(|_| do_physics(&mut world));
+
+        let kinematics = world.read_storage::<Kinematics>();
+        assert_eq!(kinematics.join().count(), 2);
+        kinematics
+            .join()
+            .for_each(|kine| assert!(is_finite(&kine.vel) && kine.vel.norm() < 1000.0));
+    }
+
+    #[test]
+    fn dense_merges_are_reproducible() {
+        let run = || {
+            let (mut world, _) = world_with(random_bodies(400, 7, Rect::new(0.0, 0.0, 60.0, 60.0)));
+            (0..300).for_each(|_| do_physics(&mut world));
+            snapshot(&world)
+        };
+
+        let first = run();
+        assert!(first.len() < 400);
+        assert_eq!(first, run());
+    }
+
+    #[test]
+    fn fast_collisions_bounce_and_slow_ones_stick() {
+        let (mut world, entities) = world_with(vec![
+            // 0.5 apart in speed, under the threshold
+            new_body([0.0, 0.0], [0.25, 0.0], 1.0, 1.0),
+            new_body([1.5, 0.0], [-0.25, 0.0], 1.0, 1.0),
+            // 4.0 apart, over it
+            new_body([100.0, 0.0], [2.0, 0.0], 1.0, 1.0),
+            new_body([101.5, 0.0], [-2.0, 0.0], 1.0, 1.0),
+        ]);
+        world.insert(StickThreshold(Some(1.0)));
+
+        calc_collisions(&mut world);
+        world.maintain();
+
+        assert!(!world.is_alive(entities[0]) && !world.is_alive(entities[1]));
+        assert!(world.is_alive(entities[2]) && world.is_alive(entities[3]));
+        let kinematics = world.read_storage::<Kinematics>();
+        assert_eq!(
+            kinematics.get(entities[2]).unwrap().vel,
+            Vector::new(-2.0, 0.0)
+        );
+        assert_eq!(
+            kinematics.get(entities[3]).unwrap().vel,
+            Vector::new(2.0, 0.0)
+        );
+        assert_eq!(world.read_storage::<Mass>().join().count(), 3);
+    }
+
+    #[test]
+    fn fast_collisions_dont_move_anchors() {
+        let (mut world, entities) = world_with(vec![
+            new_body([0.0, 0.0], [0.0, 0.0], 10.0, 1.0),
+            new_body([1.5, 0.0], [-2.0, 0.0], 1.0, 1.0),
+        ]);
+        world
+            .write_storage::<Anchored>()
+            .insert(entities[0], Anchored)
+            .unwrap();
+        world.insert(StickThreshold(Some(1.0)));
+
+        calc_collisions(&mut world);
+
+        let positions = world.read_storage::<Position>();
+        let kinematics = world.read_storage::<Kinematics>();
+        assert_eq!(positions.get(entities[0]).unwrap().0, Point::new(0.0, 0.0));
+        assert_eq!(
+            kinematics.get(entities[0]).unwrap().vel,
+            Vector::new(0.0, 0.0)
+        );
+        assert_eq!(
+            kinematics.get(entities[1]).unwrap().vel,
+            Vector::new(2.0, 0.0)
+        );
+        assert_eq!(positions.get(entities[1]).unwrap().0, Point::new(2.0, 0.0));
+    }
+
+    // a body with a NaN velocity and a finite one, with policy set
+    fn nan_world(policy: NanPolicy) -> (World, Entity, Entity) {
+        let (mut world, entities) = world_with(vec![
+            new_body([10.0, 10.0], [0.0, 0.0], 1.0, 1.0),
+            new_body([20.0, 10.0], [0.0, 0.0], 1.0, 1.0),
+        ]);
+        world.insert(policy);
+        world
+            .write_storage::<Kinematics>()
+            .get_mut(entities[0])
+            .unwrap()
+            .vel
+            .x = std::f32::NAN;
+        validate_bodies(&mut world);
+        world.maintain();
+        (world, entities[0], entities[1])
+    }
+
+    fn logged(world: &World) -> Vec<NanPolicy> {
+        world
+            .fetch::<DiagnosticsLog>()
+            .events
+            .iter()
+            .map(|event| event.policy)
+            .collect()
+    }
+
+    #[test]
+    fn nan_policy_deletes_clamps_or_ignores() {
+        let (world, bad, good) = nan_world(NanPolicy::Delete);
+        assert!(!world.is_alive(bad));
+        assert!(world.is_alive(good));
+        assert_eq!(logged(&world), vec![NanPolicy::Delete]);
+
+        let (world, bad, _) = nan_world(NanPolicy::Clamp);
+        let vel = world.read_storage::<Kinematics>().get(bad).unwrap().vel;
+        assert!(is_finite(&vel));
+        assert_eq!(logged(&world), vec![NanPolicy::Clamp]);
+
+        let (world, bad, _) = nan_world(NanPolicy::Ignore);
+        let vel = world.read_storage::<Kinematics>().get(bad).unwrap().vel;
+        assert!(world.is_alive(bad));
+        assert!(vel.x.is_nan());
+        assert_eq!(logged(&world), vec![NanPolicy::Ignore]);
+    }
+
+    #[test]
+    fn spin_adds_omega_cross_r_about_the_barycenter() {
+        let omega = 0.01;
+        let (mut world, entities) = world_with(vec![
+            new_body([0.0, 0.0], [0.5, 0.0], 3.0, 1.0),
+            new_body([30.0, 10.0], [0.0, -0.2], 1.0, 1.0),
+            new_body([-20.0, 40.0], [0.1, 0.1], 2.0, 1.0),
+            new_body([50.0, 50.0], [0.0, 0.0], 4.0, 1.0),
+        ]);
+        world
+            .write_storage::<Anchored>()
+            .insert(entities[3], Anchored)
+            .unwrap();
+        let center = center_of_mass(&world).unwrap();
+        let before = snapshot(&world);
+
+        apply_spin(&mut world, omega);
+
+        before
+            .iter()
+            .zip(snapshot(&world))
+            .for_each(|((id, pos, vel, _), (_, _, spun_vel, _))| {
+                let r = pos - center;
+                let added = spun_vel - vel;
+                if *id == entities[3].id() {
+                    assert_eq!(added, Vector::new(0.0, 0.0));
+                } else {
+                    // omega out of the plane crossed with r
+                    let expected = Vector::new(-omega * r.y, omega * r.x);
+                    assert!((added - expected).norm() < 1e-6);
+                }
+            });
+    }
+
+    #[test]
+    fn bounds_include_every_radius() {
+        let (world, _) = world_with(vec![
+            new_body([10.0, 20.0], [0.0, 0.0], 1.0, 2.0),
+            new_body([-5.0, 40.0], [0.0, 0.0], 1.0, 1.0),
+            new_body([30.0, 0.0], [0.0, 0.0], 1.0, 5.0),
+        ]);
+        assert_eq!(bounds_of(&world), Some(Rect::new(-6.0, -5.0, 41.0, 46.0)));
+        assert_eq!(bounds_of(&new_world()), None);
+    }
+}
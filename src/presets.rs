@@ -0,0 +1,170 @@
+use crate::ecs::graphics::Rect;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use specs::prelude::*;
+
+use crate::ecs::components::{Kinematics, Mass, Position};
+use crate::ecs::entities::{add_spin, create_body, new_body, Body};
+use crate::ecs::resources::{GravityConstant, Softening};
+use crate::{Point, Vector};
+
+// Ready made systems the GUI can spawn, every body is placed on a circular orbit using the
+// current GravityConstant.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Preset {
+    Binary,
+    SolarSystem,
+    RandomCloud { count: usize, seed: u64, spin: f32 },
+}
+
+// spawns the preset in bounds, usually the screen
+pub fn spawn_preset(world: &mut World, preset: Preset, bounds: Rect) {
+    let center = Point::new(bounds.x + bounds.w / 2.0, bounds.y + bounds.h / 2.0);
+    match preset {
+        Preset::Binary => spawn_binary(world, center, 60.0, 40.0),
+        Preset::SolarSystem => spawn_solar_system(world, center),
+        Preset::RandomCloud { count, seed, spin } => random_cloud(world, count, seed, bounds, spin),
+    }
+}
+
+// Speed of a circular orbit of radius r around mass, with the pull weakened by Softening. The
+// orbiting body's own mass is ignored, and there are no orbits when gravity is repulsive.
+pub fn orbital_speed(world: &World, mass: f32, r: f32) -> f32 {
+    let g = world.fetch::<GravityConstant>().0;
+    let softening = world.fetch::<Softening>().0;
+    (g.max(0.0) * mass * r.powi(2) / (r.powi(2) + softening.powi(2)).powf(1.5)).sqrt()
+}
+
+// Velocity for a circular orbit at pos around the body pulling hardest on it, going the same way
+// around as dir. The attractor's own velocity is added so the orbit follows it. None if there's
+// nothing to orbit.
+pub fn circular_orbit_velocity(world: &World, pos: Point, dir: Vector) -> Option<Vector> {
+    let positions = world.read_storage::<Position>();
+    let kinematics = world.read_storage::<Kinematics>();
+    let masses = world.read_storage::<Mass>();
+
+    let (attractor_pos, attractor_vel, attractor_mass) = (&positions, &kinematics, &masses)
+        .join()
+        .filter(|(attractor_pos, _, _)| attractor_pos.0 != pos)
+        .max_by(|(pos1, _, mass1), (pos2, _, mass2)| {
+            let pull1 = mass1.0 / (pos1.0 - pos).norm_squared();
+            let pull2 = mass2.0 / (pos2.0 - pos).norm_squared();
+            pull1
+                .partial_cmp(&pull2)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(attractor_pos, kine, mass)| (attractor_pos.0, kine.vel, mass.0))?;
+
+    let r = pos - attractor_pos;
+    let mut tangent = Vector::new(-r.y, r.x).normalize();
+    if tangent.dot(&dir) < 0.0 {
+        tangent = -tangent;
+    }
+
+    Some(attractor_vel + tangent * orbital_speed(world, attractor_mass, r.norm()))
+}
+
+// two equal bodies circling their center of mass, which stays still at center
+pub fn spawn_binary(world: &mut World, center: Point, separation: f32, mass: f32) {
+    // each body is separation / 2 from the center but pulled by a mass separation away
+    let speed = orbital_speed(world, mass, separation * 2.0);
+    let rad = mass.powf(1.0 / 3.0);
+    let offset = Vector::new(separation / 2.0, 0.0);
+    let vel = Vector::new(0.0, speed);
+
+    create_body(world, new_body(center + offset, vel, mass, rad));
+    create_body(world, new_body(center - offset, -vel, mass, rad));
+}
+
+// a star with a handful of planets at increasing distances, spread around it so they don't line up
+pub fn spawn_solar_system(world: &mut World, center: Point) {
+    let star_mass = 75.0;
+    // orbit radius, mass, radius
+    let planets = [
+        (25.0, 0.005, 0.5),
+        (45.0, 0.01, 0.8),
+        (70.0, 0.02, 1.2),
+        (100.0, 0.01, 0.8),
+        (140.0, 0.03, 2.0),
+    ];
+
+    let mut momentum = Vector::new(0.0, 0.0);
+    planets
+        .iter()
+        .enumerate()
+        .for_each(|(i, (orbit_rad, mass, rad))| {
+            let angle = i as f32 * 2.4;
+            let dir = Vector::new(angle.cos(), angle.sin());
+            let vel = Vector::new(-dir.y, dir.x) * orbital_speed(world, star_mass, *orbit_rad);
+            momentum += vel * *mass;
+
+            create_body(world, new_body(center + dir * *orbit_rad, vel, *mass, *rad));
+        });
+
+    // the star recoils so the system as a whole doesn't drift
+    create_body(
+        world,
+        new_body(center, -momentum / star_mass, star_mass, 5.0),
+    );
+}
+
+// Scatters count bodies over bounds with small random velocities, spun at spin radians per step
+// about their center of mass. The same count and seed always give the same bodies so a cloud can
+// be shared in a bug report.
+pub fn random_cloud(world: &mut World, count: usize, seed: u64, bounds: Rect, spin: f32) {
+    let mut bodies = random_bodies(count, seed, bounds);
+    add_spin(&mut bodies, spin);
+    bodies.into_iter().for_each(|body| {
+        create_body(world, body);
+    });
+}
+
+pub fn random_bodies(count: usize, seed: u64, bounds: Rect) -> Vec<Body> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..count)
+        .map(|_| {
+            let pos = Point::new(
+                bounds.x + rng.gen::<f32>() * bounds.w,
+                bounds.y + rng.gen::<f32>() * bounds.h,
+            );
+            let vel = Vector::new(rng.gen_range(-0.1, 0.1), rng.gen_range(-0.1, 0.1));
+            let mass: f32 = rng.gen_range(0.01, 2.0);
+            let rad = mass.powf(1.0 / 3.0);
+
+            new_body(pos, vel, mass, rad)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::world::new_world;
+
+    const BOUNDS: Rect = Rect {
+        x: 0.0,
+        y: 0.0,
+        w: 100.0,
+        h: 100.0,
+    };
+
+    fn cloud(seed: u64) -> Vec<(Point, Vector, f32)> {
+        let mut world = new_world();
+        random_cloud(&mut world, 30, seed, BOUNDS, 0.01);
+        let positions = world.read_storage::<Position>();
+        let kinematics = world.read_storage::<Kinematics>();
+        let masses = world.read_storage::<Mass>();
+        (&positions, &kinematics, &masses)
+            .join()
+            .map(|(pos, kine, mass)| (pos.0, kine.vel, mass.0))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_bodies() {
+        assert_eq!(random_bodies(30, 7, BOUNDS), random_bodies(30, 7, BOUNDS));
+        assert_ne!(random_bodies(30, 7, BOUNDS), random_bodies(30, 8, BOUNDS));
+        assert_eq!(cloud(7), cloud(7));
+        assert_ne!(cloud(7), cloud(8));
+    }
+}
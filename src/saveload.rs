@@ -3,6 +3,7 @@ use specs::{
     error::NoError,
     prelude::*,
     saveload::{DeserializeComponents, SerializeComponents, SimpleMarker, SimpleMarkerAllocator},
+    world::EntitiesRes,
 };
 
 use std::collections::VecDeque;
@@ -10,7 +11,9 @@ use std::fmt;
 use std::fs::File;
 use std::io::{Error, Read, Write};
 
-use crate::ecs::components::{Draw, Kinematics, Mass, Position, Radius, SaveMarker, Trail};
+use crate::ecs::components::{
+    Anchored, Draw, Kinematics, Label, Mass, Position, Radius, SaveMarker, Trail,
+};
 
 // https://github.com/amethyst/specs/blob/master/examples/saveload.rs
 
@@ -47,12 +50,23 @@ pub fn serialize_world(world: &World) -> String {
     let draws = world.read_storage::<Draw>();
     let radii = world.read_storage::<Radius>();
     let trails = world.read_storage::<Trail>();
+    let labels = world.read_storage::<Label>();
+    let anchors = world.read_storage::<Anchored>();
     let markers = world.read_storage::<SimpleMarker<SaveMarker>>();
 
     let mut ser = ron::ser::Serializer::new(Some(Default::default()), true);
 
     SerializeComponents::<NoError, SimpleMarker<SaveMarker>>::serialize(
-        &(&positions, &kinematics, &masses, &draws, &radii, &trails),
+        &(
+            &positions,
+            &kinematics,
+            &masses,
+            &draws,
+            &radii,
+            &trails,
+            &labels,
+            &anchors,
+        ),
         &entities,
         &markers,
         &mut ser,
@@ -68,6 +82,21 @@ pub fn save_world(world: &World, filename: String) -> Result<(), Error> {
     Ok(())
 }
 
+// reads every body in contents into the storages, which must be the components they were saved with
+fn deserialize_bodies<'a, S>(
+    contents: &str,
+    storages: &mut S,
+    entities: &'a EntitiesRes,
+    markers: &mut WriteStorage<'a, SimpleMarker<SaveMarker>>,
+    alloc: &mut SimpleMarkerAllocator<SaveMarker>,
+) -> Result<(), ron::de::Error>
+where
+    S: DeserializeComponents<ComboError, SimpleMarker<SaveMarker>>,
+{
+    let mut de = ron::de::Deserializer::from_str(contents)?;
+    S::deserialize(storages, entities, markers, alloc, &mut de)
+}
+
 pub fn load_world(world: &World, filename: String) -> Result<(), Error> {
     let entities = world.entities();
     let positions = world.write_storage::<Position>();
@@ -77,27 +106,57 @@ pub fn load_world(world: &World, filename: String) -> Result<(), Error> {
     let radii = world.write_storage::<Radius>();
     let mut markers = world.write_storage::<SimpleMarker<SaveMarker>>();
     let mut trails = world.write_storage::<Trail>();
+    let labels = world.write_storage::<Label>();
+    let anchors = world.write_storage::<Anchored>();
     let mut alloc = world.write_resource::<SimpleMarkerAllocator<SaveMarker>>();
 
     (&mut trails).join().for_each(|mut trail| {
         trail.points = VecDeque::with_capacity(0);
     });
 
-    use ron::de::Deserializer;
-
     let mut file = File::open(filename)?;
     let mut file_contents = String::new();
     file.read_to_string(&mut file_contents)?;
 
-    if let Ok(mut de) = Deserializer::from_str(&file_contents) {
-        DeserializeComponents::<ComboError, _>::deserialize(
-            &mut (positions, kinematics, masses, draws, radii, trails),
+    // Saves from before anchors and labels have fewer components per body, so they're read again
+    // with fewer until one fits. A save that doesn't fit fails on its first body, before anything
+    // is created.
+    let loaded = {
+        let mut with_anchors = (
+            positions, kinematics, masses, draws, radii, trails, labels, anchors,
+        );
+        deserialize_bodies(
+            &file_contents,
+            &mut with_anchors,
             &entities,
             &mut markers,
             &mut alloc,
-            &mut de,
         )
-        .unwrap_or_else(|e| eprintln!("Error: {}", e));
+        .or_else(|e| {
+            let (positions, kinematics, masses, draws, radii, trails, labels, _) = with_anchors;
+            let mut with_labels = (positions, kinematics, masses, draws, radii, trails, labels);
+            deserialize_bodies(
+                &file_contents,
+                &mut with_labels,
+                &entities,
+                &mut markers,
+                &mut alloc,
+            )
+            .or_else(|_| {
+                let (positions, kinematics, masses, draws, radii, trails, _) = with_labels;
+                deserialize_bodies(
+                    &file_contents,
+                    &mut (positions, kinematics, masses, draws, radii, trails),
+                    &entities,
+                    &mut markers,
+                    &mut alloc,
+                )
+            })
+            .map_err(|_| e)
+        })
+    };
+    if let Err(e) = loaded {
+        eprintln!("Error: {}", e);
     }
 
     let mut trails = world.write_storage::<Trail>();
@@ -0,0 +1,57 @@
+use serde::Deserialize;
+
+use crate::ecs::components::Draw;
+use crate::ecs::entities::{new_body, Body};
+
+use std::fs;
+
+// The bodies the sim starts with, read from a ron file given with `--scene`, e.g.
+// (bodies: [
+//     (pos: (150, 100), mass: 75, rad: 5),
+//     (pos: (215, 100), vel: (0, -1.1), mass: 0.01, rad: 0.8, color: (1, 0.5, 0, 1)),
+// ])
+// vel defaults to still and color to white. Unlike a save this is only read at startup.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct SceneConfig {
+    pub bodies: Vec<SceneBody>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct SceneBody {
+    pub pos: (f32, f32),
+    #[serde(default)]
+    pub vel: (f32, f32),
+    pub mass: f32,
+    pub rad: f32,
+    #[serde(default = "white")]
+    pub color: (f32, f32, f32, f32),
+}
+
+fn white() -> (f32, f32, f32, f32) {
+    (1.0, 1.0, 1.0, 1.0)
+}
+
+impl SceneConfig {
+    pub fn bodies(&self) -> Vec<Body> {
+        self.bodies
+            .iter()
+            .map(|body| {
+                let mut new = new_body(
+                    [body.pos.0, body.pos.1],
+                    [body.vel.0, body.vel.1],
+                    body.mass,
+                    body.rad,
+                );
+                new.3 = Draw([body.color.0, body.color.1, body.color.2, body.color.3].into());
+                new
+            })
+            .collect()
+    }
+}
+
+// the error says whether the file couldn't be read or isn't a scene
+pub fn load_scene(path: &str) -> Result<SceneConfig, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path, e))?;
+    ron::de::from_str(&contents).map_err(|e| format!("{} isn't a valid scene: {}", path, e))
+}
@@ -0,0 +1,18 @@
+use ggez::{filesystem, graphics, Context, GameResult};
+
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Writes everything drawn so far this frame to a png named after the current time in ggez's user
+// data folder, and returns where it went. Taking it before the GUI is drawn leaves the menus out.
+pub fn save_screenshot(ctx: &mut Context) -> GameResult<PathBuf> {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or(0);
+    let filename = format!("screenshot_{}.png", millis);
+
+    // ggez paths are relative to its own folders and have to start with a slash
+    graphics::screenshot(ctx)?.encode(ctx, graphics::ImageFormat::Png, format!("/{}", filename))?;
+    Ok(filesystem::user_data_dir(ctx).join(filename))
+}
@@ -0,0 +1,95 @@
+use specs::prelude::*;
+
+use crate::ecs::components::{Kinematics, Mass, Position, Radius};
+use crate::ecs::entities::{create_body, Body};
+use crate::ecs::resources::DT;
+use crate::ecs::world::new_world;
+use crate::physics::do_physics;
+
+/// A world stepped by the same physics as the window, with every other resource left at its
+/// default. The world is public so anything else in `resources` can still be changed.
+///
+/// ```
+/// use gravity_v2::ecs::entities::new_body;
+/// use gravity_v2::resources::Softening;
+/// use gravity_v2::Simulation;
+///
+/// let mut sim = Simulation::new();
+/// // softening weakens the pull up close, without it the orbit is exactly circular
+/// sim.world.insert(Softening(0.0));
+/// // a light body on a circular orbit around a heavy one, v = sqrt(G * M / r)
+/// sim.add_body(new_body([150.0, 100.0], [0.0, 0.0], 100.0, 2.0));
+/// sim.add_body(new_body([160.0, 100.0], [0.0, 12.0f32.sqrt()], 0.01, 0.5));
+///
+/// (0..200).for_each(|_| sim.step(0.05));
+///
+/// let bodies: Vec<_> = sim.bodies().collect();
+/// assert_eq!(bodies.len(), 2);
+/// let dist = bodies[0].1.dist(bodies[1].1);
+/// assert!((dist - 10.0).abs() < 0.1);
+/// ```
+pub struct Simulation {
+    pub world: World,
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Simulation { world: new_world() }
+    }
+
+    pub fn add_body(&mut self, body: Body) -> Entity {
+        create_body(&mut self.world, body)
+    }
+
+    // one step of dt, which stays the world's DT afterwards
+    pub fn step(&mut self, dt: f32) {
+        self.world.insert(DT(dt));
+        do_physics(&mut self.world);
+    }
+
+    // every body that's still around after merging, in entity order
+    pub fn bodies(&self) -> impl Iterator<Item = (Entity, Position, Kinematics, Mass, Radius)> {
+        let entities = self.world.entities();
+        let positions = self.world.read_storage::<Position>();
+        let kinematics = self.world.read_storage::<Kinematics>();
+        let masses = self.world.read_storage::<Mass>();
+        let radii = self.world.read_storage::<Radius>();
+
+        (&entities, &positions, &kinematics, &masses, &radii)
+            .join()
+            .map(|(entity, pos, kine, mass, rad)| (entity, *pos, *kine, *mass, *rad))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation::new()
+    }
+}
+
+// Steps the physics without a window, nothing here needs a ggez Context so it works on any world
+// made with ecs::world::new_world.
+pub fn run_headless(world: &mut World, steps: usize, dt: f32) {
+    world.insert(DT(dt));
+    (0..steps).for_each(|_| do_physics(world));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::entities::new_body;
+
+    #[test]
+    fn steps_without_a_window() {
+        let mut world = new_world();
+        let body = create_body(&mut world, new_body([0.0, 0.0], [1.0, 0.0], 1.0, 1.0));
+
+        run_headless(&mut world, 10, 0.5);
+
+        let pos = world.read_storage::<Position>().get(body).unwrap().0;
+        assert!((pos.x - 5.0).abs() < 1e-4);
+        assert_eq!(pos.y, 0.0);
+    }
+}